@@ -15,13 +15,28 @@
 extern crate exonum;
 extern crate exonum_btc_anchoring as anchoring;
 extern crate exonum_configuration as configuration;
+extern crate structopt;
 
 use exonum::helpers;
 use exonum::helpers::fabric::NodeBuilder;
+use structopt::StructOpt;
+
+use std::env;
+use std::process;
 
 fn main() {
     exonum::crypto::init();
     helpers::init_logger().unwrap();
+    // Anchoring keys rotation subcommands are not the node ones, so they are handled separately.
+    if env::args().nth(1).as_ref().map(String::as_str) == Some("btc-anchoring") {
+        let command = anchoring::KeysCommand::from_iter(env::args().skip(1));
+        if let Err(e) = command.run() {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     let node = NodeBuilder::new()
         .with_service(Box::new(configuration::ServiceFactory))
         .with_service(Box::new(anchoring::ServiceFactory));
@@ -15,12 +15,12 @@
 //! Anchoring HTTP API implementation.
 
 use exonum::api::{self, ServiceApiBuilder, ServiceApiState};
-use exonum::blockchain::{BlockProof, Schema as CoreSchema};
+use exonum::blockchain::{Block, BlockProof, Schema as CoreSchema};
 use exonum::crypto::Hash;
 use exonum::helpers::Height;
-use exonum::storage::{ListProof, MapProof};
+use exonum::storage::{ListProof, MapProof, ProofListIndex, Snapshot};
 
-use failure::Fail;
+use failure::{format_err, Fail};
 use serde_derive::{Deserialize, Serialize};
 
 use std::cmp::{
@@ -28,8 +28,13 @@ use std::cmp::{
     Ordering::{self, Equal, Greater, Less},
 };
 
-use crate::blockchain::BtcAnchoringSchema;
-use crate::btc;
+use crate::blockchain::{
+    data_layout::{AnchoringSigners, SignerStats},
+    BtcAnchoringSchema, BtcAnchoringState,
+};
+use crate::btc::{self, BuilderError};
+use crate::config::{GlobalConfig, SignerId};
+use crate::service::KeyPool;
 use crate::BTC_ANCHORING_SERVICE_ID;
 
 /// Query parameters for the find transaction request.
@@ -46,6 +51,29 @@ pub struct HeightQuery {
     pub height: u64,
 }
 
+/// Query parameters for the anchoring transaction signers request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TxidQuery {
+    /// Identifier of the anchoring transaction.
+    pub txid: Hash,
+}
+
+/// Query parameters for the private key removal request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressQuery {
+    /// Anchoring address.
+    pub address: btc::Address,
+}
+
+/// Anchoring address and the corresponding private key of the node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BitcoinKeypair {
+    /// Anchoring address.
+    pub address: btc::Address,
+    /// Corresponding private key.
+    pub private_key: btc::PrivateKey,
+}
+
 /// A proof of existence for an anchoring transaction at the given height.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionProof {
@@ -70,6 +98,94 @@ pub struct BlockHeaderProof {
     pub to_block_header: ListProof<Hash>,
 }
 
+/// Header of the anchored Exonum block along with the proof of existence of the anchoring
+/// transaction which commits to it.
+///
+/// The block hash and, if it is committed, the state hash from the anchoring payload should
+/// match the given header, so the `state_hash` of the header can be used as a trusted root for
+/// the proofs of the services data.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchoredBlockProof {
+    /// Header of the anchored block.
+    pub block: Block,
+    /// Proof for the anchoring transaction which commits to the block.
+    pub anchoring_transaction: TransactionProof,
+}
+
+/// A proof of existence or absence for the signers record of the anchoring transaction.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchoringSignersProof {
+    /// Latest authorized block in the blockchain.
+    pub latest_authorized_block: BlockProof,
+    /// Proof for the whole database table.
+    pub to_table: MapProof<Hash, Hash>,
+    /// Proof for the signers record of the specific transaction in this table.
+    pub to_signers: MapProof<Hash, AnchoringSigners>,
+}
+
+/// Anchoring participation statistics of the signer from the actual configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignerStatsInfo {
+    /// Signer identifier.
+    pub signer: SignerId,
+    /// Bitcoin public key of the signer.
+    pub public_key: btc::PublicKey,
+    /// Participation statistics.
+    pub stats: SignerStats,
+    /// Average latency in blocks between the proposal availability and the signature.
+    pub average_latency: Option<f64>,
+}
+
+/// Current status of the anchoring service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum AnchoringStatus {
+    /// The usual anchoring workflow.
+    Regular {
+        /// Actual anchoring address.
+        address: btc::Address,
+    },
+    /// The transition from the actual anchoring address to the following one.
+    Transition {
+        /// Actual anchoring address.
+        address: btc::Address,
+        /// Following anchoring address.
+        following_address: btc::Address,
+    },
+    /// The transition is blocked since the funds are insufficient to pay the fee of
+    /// the transition transaction.
+    TransitionBlocked {
+        /// Actual anchoring address.
+        address: btc::Address,
+        /// Following anchoring address.
+        following_address: btc::Address,
+        /// Amount of the funds in satoshis lacking to pay the fee.
+        missing_funds: u64,
+    },
+    /// Anchoring is suspended since there is no valid anchoring configuration.
+    Misconfigured {
+        /// Description of the configuration error.
+        error: String,
+    },
+}
+
+/// Details of the transition from the actual anchoring address to the following one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionInfo {
+    /// Actual anchoring address.
+    pub actual_address: btc::Address,
+    /// Following anchoring address.
+    pub following_address: btc::Address,
+    /// Identifier of the finalized transition transaction which moves the funds to
+    /// the following address, if it has been created yet.
+    pub transition_transaction: Option<Hash>,
+    /// Height since which the following configuration becomes actual.
+    pub actual_from: Height,
+    /// Amount of the funds in satoshis lacking to pay the fee of the transition transaction,
+    /// if the transition is blocked because of insufficient funds.
+    pub missing_funds: Option<u64>,
+}
+
 /// Public API specification for the Exonum Bitcoin anchoring service.
 pub trait PublicApi {
     /// Error type for the current public API implementation.
@@ -101,6 +217,38 @@ pub trait PublicApi {
     ///
     /// `GET /{api_prefix}/v1/block_header_proof?height={height}`
     fn block_header_proof(&self, query: HeightQuery) -> Result<BlockHeaderProof, Self::Error>;
+
+    /// Returns the header of the block anchored at the given height along with the proof
+    /// for the corresponding anchoring transaction, or `None` if the block at the given
+    /// height has not been anchored.
+    ///
+    /// `GET /{api_prefix}/v1/anchored_block?height={height}`
+    fn anchored_block(&self, query: HeightQuery)
+        -> Result<Option<AnchoredBlockProof>, Self::Error>;
+
+    /// Returns the proof for the record of signers whose signatures got into
+    /// the finalized anchoring transaction with the given identifier.
+    ///
+    /// `GET /{api_prefix}/v1/transaction/signers?txid={txid}`
+    fn anchoring_signers(&self, query: TxidQuery) -> Result<AnchoringSignersProof, Self::Error>;
+
+    /// Returns the anchoring participation statistics for the signers of the actual
+    /// configuration. The signer which keeps missing anchors may have lost its anchoring key.
+    ///
+    /// `GET /{api_prefix}/v1/validators/stats`
+    fn signers_stats(&self, _query: ()) -> Result<Vec<SignerStatsInfo>, Self::Error>;
+
+    /// Returns the current status of the anchoring service. If the service is misconfigured,
+    /// anchoring is suspended until a valid anchoring configuration becomes actual.
+    ///
+    /// `GET /{api_prefix}/v1/status`
+    fn status(&self, _query: ()) -> Result<AnchoringStatus, Self::Error>;
+
+    /// Returns the details of the transition to the following anchoring address or `None`
+    /// if the anchoring is not in the transition state.
+    ///
+    /// `GET /{api_prefix}/v1/transition`
+    fn transition(&self, _query: ()) -> Result<Option<TransitionInfo>, Self::Error>;
 }
 
 impl PublicApi for ServiceApiState {
@@ -109,7 +257,7 @@ impl PublicApi for ServiceApiState {
     fn actual_address(&self, _query: ()) -> Result<btc::Address, Self::Error> {
         let snapshot = self.snapshot();
         let schema = BtcAnchoringSchema::new(snapshot);
-        Ok(schema.actual_configuration().anchoring_address())
+        Ok(actual_configuration(&schema)?.anchoring_address())
     }
 
     fn following_address(&self, _query: ()) -> Result<Option<btc::Address>, Self::Error> {
@@ -133,58 +281,65 @@ impl PublicApi for ServiceApiState {
         }
 
         let tx_index = if let Some(height) = query.height {
-            // Handmade binary search.
-            let f = |index| -> Ordering {
-                // index is always in [0, size), that means index is >= 0 and < size.
-                // index >= 0: by definition
-                // index < size: index = size / 2 + size / 4 + size / 8 ...
-                let other = tx_chain
-                    .get(index)
-                    .unwrap()
-                    .anchoring_payload()
-                    .unwrap()
-                    .block_height;
-                other.cmp(&height)
-            };
-
-            let mut base = 0;
-            let mut size = tx_chain.len();
-            while size > 1 {
-                let half = size / 2;
-                let mid = base + half;
-                let cmp = f(mid);
-                base = if cmp == Greater { base } else { mid };
-                size -= half;
-            }
-            // Don't forget to check base value.
-            let cmp = f(base);
-            if cmp == Equal {
-                base
-            } else {
-                cmp::min(base + (cmp == Less) as u64, tx_chain.len() - 1)
-            }
+            find_transaction_index(&tx_chain, height)
         } else {
             tx_chain.len() - 1
         };
 
-        let core_schema = CoreSchema::new(&snapshot);
+        Ok(Some(transaction_proof(&snapshot, &tx_chain, tx_index)))
+    }
+
+    fn block_header_proof(&self, query: HeightQuery) -> Result<BlockHeaderProof, Self::Error> {
+        let view = self.snapshot();
+        let core_schema = CoreSchema::new(&view);
+        let anchoring_schema = BtcAnchoringSchema::new(&view);
+
         let max_height = core_schema.block_hashes_by_height().len() - 1;
+
         let latest_authorized_block = core_schema
             .block_and_precommits(Height(max_height))
             .unwrap();
         let to_table: MapProof<Hash, Hash> =
-            core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 0);
-        let to_transaction = tx_chain.get_proof(tx_index);
+            core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 3);
+        let to_block_header = anchoring_schema.anchored_blocks().get_proof(query.height);
 
-        Ok(Some(TransactionProof {
+        Ok(BlockHeaderProof {
             latest_authorized_block,
             to_table,
-            to_transaction,
-            transactions_count: tx_chain.len(),
+            to_block_header,
+        })
+    }
+
+    fn anchored_block(
+        &self,
+        query: HeightQuery,
+    ) -> Result<Option<AnchoredBlockProof>, Self::Error> {
+        let snapshot = self.snapshot();
+        let anchoring_schema = BtcAnchoringSchema::new(&snapshot);
+        let tx_chain = anchoring_schema.anchoring_transactions_chain();
+
+        if tx_chain.is_empty() {
+            return Ok(None);
+        }
+
+        let height = Height(query.height);
+        let tx_index = find_transaction_index(&tx_chain, height);
+        let payload = tx_chain.get(tx_index).unwrap().anchoring_payload().unwrap();
+        if payload.block_height != height {
+            return Ok(None);
+        }
+
+        let block = CoreSchema::new(&snapshot)
+            .blocks()
+            .get(&payload.block_hash)
+            .unwrap();
+        Ok(Some(AnchoredBlockProof {
+            block,
+            anchoring_transaction: transaction_proof(&snapshot, &tx_chain, tx_index),
         }))
     }
 
-    fn block_header_proof(&self, query: HeightQuery) -> Result<BlockHeaderProof, Self::Error> {
+    fn anchoring_signers(&self, query: TxidQuery) -> Result<AnchoringSignersProof, Self::Error> {
         let view = self.snapshot();
         let core_schema = CoreSchema::new(&view);
         let anchoring_schema = BtcAnchoringSchema::new(&view);
@@ -195,22 +350,359 @@ impl PublicApi for ServiceApiState {
             .block_and_precommits(Height(max_height))
             .unwrap();
         let to_table: MapProof<Hash, Hash> =
-            core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 3);
-        let to_block_header = anchoring_schema.anchored_blocks().get_proof(query.height);
+            core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 4);
+        let to_signers = anchoring_schema.anchoring_signers().get_proof(query.txid);
 
-        Ok(BlockHeaderProof {
+        Ok(AnchoringSignersProof {
             latest_authorized_block,
             to_table,
-            to_block_header,
+            to_signers,
+        })
+    }
+
+    fn signers_stats(&self, _query: ()) -> Result<Vec<SignerStatsInfo>, Self::Error> {
+        let snapshot = self.snapshot();
+        let schema = BtcAnchoringSchema::new(snapshot);
+        let signers_stats = schema.signers_stats();
+        Ok(actual_configuration(&schema)?
+            .public_keys
+            .into_iter()
+            .enumerate()
+            .map(|(index, public_key)| {
+                let signer = SignerId(index as u16);
+                let stats = signers_stats.get(&public_key).unwrap_or_default();
+                SignerStatsInfo {
+                    signer,
+                    public_key,
+                    stats,
+                    average_latency: stats.average_latency(),
+                }
+            })
+            .collect())
+    }
+
+    fn status(&self, _query: ()) -> Result<AnchoringStatus, Self::Error> {
+        let snapshot = self.snapshot();
+        let schema = BtcAnchoringSchema::new(snapshot);
+        Ok(match schema.try_actual_state() {
+            Ok(BtcAnchoringState::Regular {
+                actual_configuration,
+            }) => AnchoringStatus::Regular {
+                address: actual_configuration.anchoring_address(),
+            },
+            Ok(state) => {
+                let address = state.actual_configuration().anchoring_address();
+                let following_address = state.output_address();
+                match missing_funds(&schema, &state) {
+                    Some(missing_funds) => AnchoringStatus::TransitionBlocked {
+                        address,
+                        following_address,
+                        missing_funds,
+                    },
+                    None => AnchoringStatus::Transition {
+                        address,
+                        following_address,
+                    },
+                }
+            }
+            Err(e) => AnchoringStatus::Misconfigured {
+                error: e.to_string(),
+            },
         })
     }
+
+    fn transition(&self, _query: ()) -> Result<Option<TransitionInfo>, Self::Error> {
+        let snapshot = self.snapshot();
+        let schema = BtcAnchoringSchema::new(&snapshot);
+        let state = schema
+            .try_actual_state()
+            .map_err(|e| api::Error::InternalError(e.into()))?;
+        let following_configuration = match state.following_configuration() {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        let actual_from = CoreSchema::new(&snapshot)
+            .following_configuration()
+            .map(|config| config.actual_from)
+            .ok_or_else(|| api::Error::InternalError(format_err!("No following configuration")))?;
+
+        // The transition transaction is the latest one if it spends to the following address.
+        let outgoing_script_pubkey = state.script_pubkey();
+        let transition_transaction = schema
+            .anchoring_transactions_chain()
+            .last()
+            .filter(|tx| tx.0.output[0].script_pubkey == outgoing_script_pubkey)
+            .map(|tx| tx.id());
+        let missing_funds = missing_funds(&schema, &state);
+
+        Ok(Some(TransitionInfo {
+            actual_address: state.actual_configuration().anchoring_address(),
+            following_address: following_configuration.anchoring_address(),
+            transition_transaction,
+            actual_from,
+            missing_funds,
+        }))
+    }
 }
 
-pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+/// Private API specification for the anchoring service. It allows to change the anchoring
+/// private keys of the node without the node restart.
+pub trait PrivateApi {
+    /// Error type for the current private API implementation.
+    type Error: Fail;
+
+    /// Returns the list of the anchoring addresses for which the node has private keys.
+    ///
+    /// `GET /{api_prefix}/v1/private_keys`
+    fn private_keys(&self, _query: ()) -> Result<Vec<btc::Address>, Self::Error>;
+
+    /// Adds the private key for the given anchoring address. The address should be either
+    /// the actual, the following or a retired anchoring address and the private key should
+    /// correspond to the public key of this node in its redeem script.
+    ///
+    /// `POST /{api_prefix}/v1/private_keys`
+    fn add_private_key(&self, keypair: BitcoinKeypair) -> Result<(), Self::Error>;
+
+    /// Removes the private key for the given anchoring address.
+    ///
+    /// `POST /{api_prefix}/v1/private_keys/remove`
+    fn remove_private_key(&self, query: AddressQuery) -> Result<(), Self::Error>;
+}
+
+struct PrivateApiImpl<'a> {
+    state: &'a ServiceApiState,
+    private_keys: &'a KeyPool,
+}
+
+impl<'a> PrivateApi for PrivateApiImpl<'a> {
+    type Error = api::Error;
+
+    fn private_keys(&self, _query: ()) -> Result<Vec<btc::Address>, Self::Error> {
+        let mut addresses = self
+            .private_keys
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        addresses.sort();
+        Ok(addresses)
+    }
+
+    fn add_private_key(&self, keypair: BitcoinKeypair) -> Result<(), Self::Error> {
+        let schema = BtcAnchoringSchema::new(self.state.snapshot());
+        let service_key = self.state.public_key();
+        let config = Some(actual_configuration(&schema)?)
+            .into_iter()
+            .chain(schema.following_configuration())
+            .find(|config| config.anchoring_address() == keypair.address);
+        // Finds the identifier of this node among the signers of the address.
+        let (config, signer_id) = if let Some(config) = config {
+            let signer_id = if config.signers.is_empty() {
+                CoreSchema::new(self.state.snapshot())
+                    .actual_configuration()
+                    .validator_keys
+                    .iter()
+                    .position(|keys| &keys.service_key == service_key)
+                    .map(|id| SignerId(id as u16))
+            } else {
+                config.signer_id(service_key)
+            };
+            (config, signer_id)
+        } else {
+            // Keys of the retired addresses are needed to sweep the funds sent to them.
+            let retired = schema
+                .retired_configurations()
+                .into_iter()
+                .find(|retired| retired.configuration.anchoring_address() == keypair.address)
+                .ok_or_else(|| {
+                    api::Error::BadRequest(format!(
+                        "Address {} is neither the actual, the following nor a retired \
+                         anchoring address.",
+                        keypair.address
+                    ))
+                })?;
+            let signer_id = retired.signer_id(service_key);
+            (retired.configuration, signer_id)
+        };
+
+        if keypair.private_key.0.network != config.network {
+            return Err(api::Error::BadRequest(format!(
+                "Private key doesn't belong to the {} network.",
+                config.network
+            )));
+        }
+        let expected_public_key = signer_id
+            .and_then(|signer_id| config.public_keys.get(signer_id.0 as usize))
+            .ok_or_else(|| {
+                api::Error::BadRequest(format!(
+                    "This node is not an anchoring signer for the address {}.",
+                    keypair.address
+                ))
+            })?;
+
+        if &keypair.private_key.public_key() != expected_public_key {
+            return Err(api::Error::BadRequest(format!(
+                "Private key doesn't correspond to the public key {} of this node.",
+                expected_public_key
+            )));
+        }
+
+        self.private_keys
+            .write()
+            .unwrap()
+            .insert(keypair.address, keypair.private_key);
+        Ok(())
+    }
+
+    fn remove_private_key(&self, query: AddressQuery) -> Result<(), Self::Error> {
+        self.private_keys.write().unwrap().remove(&query.address);
+        Ok(())
+    }
+}
+
+/// Returns the actual anchoring configuration or an internal error if the anchoring service
+/// is misconfigured.
+fn actual_configuration<T>(schema: &BtcAnchoringSchema<T>) -> Result<GlobalConfig, api::Error>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    schema
+        .try_actual_configuration()
+        .map_err(|e| api::Error::InternalError(e.into()))
+}
+
+/// Returns the amount of the funds lacking to pay the fee of the proposed anchoring
+/// transaction if the funds are insufficient.
+fn missing_funds<T>(schema: &BtcAnchoringSchema<T>, state: &BtcAnchoringState) -> Option<u64>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    match schema.proposed_anchoring_transaction(state) {
+        Some(Err(BuilderError::InsufficientFunds { total_fee, balance })) => {
+            Some(total_fee.saturating_sub(balance))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the index of the anchoring transaction with the height that is greater or equal
+/// to the given one or the index of the latest anchoring transaction if there is no such one.
+fn find_transaction_index<T>(tx_chain: &ProofListIndex<T, btc::Transaction>, height: Height) -> u64
+where
+    T: AsRef<dyn Snapshot>,
+{
+    // Handmade binary search.
+    let f = |index| -> Ordering {
+        // index is always in [0, size), that means index is >= 0 and < size.
+        // index >= 0: by definition
+        // index < size: index = size / 2 + size / 4 + size / 8 ...
+        let other = tx_chain
+            .get(index)
+            .unwrap()
+            .anchoring_payload()
+            .unwrap()
+            .block_height;
+        other.cmp(&height)
+    };
+
+    let mut base = 0;
+    let mut size = tx_chain.len();
+    while size > 1 {
+        let half = size / 2;
+        let mid = base + half;
+        let cmp = f(mid);
+        base = if cmp == Greater { base } else { mid };
+        size -= half;
+    }
+    // Don't forget to check base value.
+    let cmp = f(base);
+    if cmp == Equal {
+        base
+    } else {
+        cmp::min(base + (cmp == Less) as u64, tx_chain.len() - 1)
+    }
+}
+
+/// Creates a proof of existence for the anchoring transaction with the given index.
+fn transaction_proof<S, T>(
+    snapshot: S,
+    tx_chain: &ProofListIndex<T, btc::Transaction>,
+    tx_index: u64,
+) -> TransactionProof
+where
+    S: AsRef<dyn Snapshot>,
+    T: AsRef<dyn Snapshot>,
+{
+    let core_schema = CoreSchema::new(snapshot);
+    let max_height = core_schema.block_hashes_by_height().len() - 1;
+    let latest_authorized_block = core_schema
+        .block_and_precommits(Height(max_height))
+        .unwrap();
+    let to_table: MapProof<Hash, Hash> =
+        core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 0);
+    let to_transaction = tx_chain.get_proof(tx_index);
+
+    TransactionProof {
+        latest_authorized_block,
+        to_table,
+        to_transaction,
+        transactions_count: tx_chain.len(),
+    }
+}
+
+pub(crate) fn wire(builder: &mut ServiceApiBuilder, private_keys: Option<KeyPool>) {
+    if let Some(private_keys) = private_keys {
+        wire_private(builder, private_keys);
+    }
+
     builder
         .public_scope()
         .endpoint("v1/address/actual", ServiceApiState::actual_address)
         .endpoint("v1/address/following", ServiceApiState::following_address)
         .endpoint("v1/transaction", ServiceApiState::find_transaction)
-        .endpoint("v1/block_header_proof", ServiceApiState::block_header_proof);
+        .endpoint("v1/block_header_proof", ServiceApiState::block_header_proof)
+        .endpoint("v1/anchored_block", ServiceApiState::anchored_block)
+        .endpoint("v1/transaction/signers", ServiceApiState::anchoring_signers)
+        .endpoint("v1/validators/stats", ServiceApiState::signers_stats)
+        .endpoint("v1/status", ServiceApiState::status)
+        .endpoint("v1/transition", ServiceApiState::transition);
+}
+
+fn wire_private(builder: &mut ServiceApiBuilder, private_keys: KeyPool) {
+    let keys_list = private_keys.clone();
+    let keys_add = private_keys.clone();
+    let keys_remove = private_keys;
+    builder
+        .private_scope()
+        .endpoint(
+            "v1/private_keys",
+            move |state: &ServiceApiState, query: ()| {
+                PrivateApiImpl {
+                    state,
+                    private_keys: &keys_list,
+                }
+                .private_keys(query)
+            },
+        )
+        .endpoint_mut(
+            "v1/private_keys",
+            move |state: &ServiceApiState, keypair: BitcoinKeypair| {
+                PrivateApiImpl {
+                    state,
+                    private_keys: &keys_add,
+                }
+                .add_private_key(keypair)
+            },
+        )
+        .endpoint_mut(
+            "v1/private_keys/remove",
+            move |state: &ServiceApiState, query: AddressQuery| {
+                PrivateApiImpl {
+                    state,
+                    private_keys: &keys_remove,
+                }
+                .remove_private_key(query)
+            },
+        );
 }
@@ -0,0 +1,89 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum::crypto::{self, CryptoHash, Hash};
+use exonum::helpers::ValidatorId;
+use exonum::storage::StorageValue;
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+
+/// Votes of the validators for the out-of-schedule anchoring of the block.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringRequestVotes {
+    /// Validators bitmap, the bit with the given index is set if the validator with the
+    /// same identifier has requested the anchoring.
+    pub bitmap: Vec<u8>,
+}
+
+impl AnchoringRequestVotes {
+    /// Adds the vote of the validator with the given identifier.
+    pub fn insert(&mut self, id: ValidatorId) {
+        let index = id.0 as usize;
+        if self.bitmap.len() <= index / 8 {
+            self.bitmap.resize(index / 8 + 1, 0);
+        }
+        self.bitmap[index / 8] |= 1 << (index % 8);
+    }
+
+    /// Checks that the validator with the given identifier has voted.
+    pub fn contains(&self, id: ValidatorId) -> bool {
+        let index = id.0 as usize;
+        self.bitmap
+            .get(index / 8)
+            .map_or(false, |byte| byte & (1 << (index % 8)) != 0)
+    }
+
+    /// Returns the number of votes.
+    pub fn count(&self) -> usize {
+        self.bitmap
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+}
+
+impl StorageValue for AnchoringRequestVotes {
+    fn into_bytes(self) -> Vec<u8> {
+        self.bitmap
+    }
+
+    fn from_bytes(value: Cow<[u8]>) -> Self {
+        Self {
+            bitmap: value.into_owned(),
+        }
+    }
+}
+
+impl CryptoHash for AnchoringRequestVotes {
+    fn hash(&self) -> Hash {
+        crypto::hash(&self.bitmap)
+    }
+}
+
+#[test]
+fn test_anchoring_request_votes() {
+    let mut votes = AnchoringRequestVotes::default();
+    votes.insert(ValidatorId(1));
+    votes.insert(ValidatorId(9));
+    votes.insert(ValidatorId(9));
+    assert!(votes.contains(ValidatorId(9)));
+    assert!(!votes.contains(ValidatorId(2)));
+    assert!(!votes.contains(ValidatorId(100)));
+    assert_eq!(votes.count(), 2);
+
+    let bytes = votes.clone().into_bytes();
+    assert_eq!(AnchoringRequestVotes::from_bytes(bytes.into()), votes);
+}
@@ -0,0 +1,105 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum::crypto::{self, CryptoHash, Hash};
+use exonum::helpers::Height;
+use exonum::storage::StorageValue;
+
+use byteorder::{ByteOrder, LittleEndian};
+use serde_derive::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+
+use crate::config::SignerId;
+
+/// Audit record of the finalized anchoring transaction which contains the signers
+/// whose signatures got into the transaction witness.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringSigners {
+    /// Signers bitmap, the bit with the given index is set if the signer with the
+    /// same identifier has signed at least one of the transaction inputs.
+    pub bitmap: Vec<u8>,
+    /// Height of the Exonum block in which the transaction has been finalized.
+    pub finalization_height: Height,
+}
+
+impl AnchoringSigners {
+    /// Creates an empty record for the given signers count.
+    pub fn new(signers_count: usize, finalization_height: Height) -> Self {
+        Self {
+            bitmap: vec![0; (signers_count + 7) / 8],
+            finalization_height,
+        }
+    }
+
+    /// Marks the signer with the given identifier as a participant.
+    pub fn insert(&mut self, id: SignerId) {
+        let index = id.0 as usize;
+        self.bitmap[index / 8] |= 1 << (index % 8);
+    }
+
+    /// Checks that the signer with the given identifier is a participant.
+    pub fn contains(&self, id: SignerId) -> bool {
+        let index = id.0 as usize;
+        self.bitmap
+            .get(index / 8)
+            .map_or(false, |byte| byte & (1 << (index % 8)) != 0)
+    }
+
+    /// Returns identifiers of the participated signers.
+    pub fn signers(&self) -> Vec<SignerId> {
+        (0..self.bitmap.len() * 8)
+            .map(|index| SignerId(index as u16))
+            .filter(|id| self.contains(*id))
+            .collect()
+    }
+}
+
+impl StorageValue for AnchoringSigners {
+    fn into_bytes(self) -> Vec<u8> {
+        let mut buf = vec![0_u8; 8];
+        LittleEndian::write_u64(&mut buf, self.finalization_height.0);
+        buf.extend_from_slice(&self.bitmap);
+        buf
+    }
+
+    fn from_bytes(value: Cow<[u8]>) -> Self {
+        let bytes = value.as_ref();
+        Self {
+            finalization_height: Height(LittleEndian::read_u64(&bytes[0..8])),
+            bitmap: bytes[8..].to_vec(),
+        }
+    }
+}
+
+impl CryptoHash for AnchoringSigners {
+    fn hash(&self) -> Hash {
+        crypto::hash(&self.clone().into_bytes())
+    }
+}
+
+#[test]
+fn test_anchoring_signers_storage_value() {
+    let mut signers = AnchoringSigners::new(10, Height(42));
+    signers.insert(SignerId(1));
+    signers.insert(SignerId(9));
+    assert!(signers.contains(SignerId(9)));
+    assert!(!signers.contains(SignerId(2)));
+    assert!(!signers.contains(SignerId(100)));
+    assert_eq!(signers.signers(), vec![SignerId(1), SignerId(9)]);
+
+    let bytes = signers.clone().into_bytes();
+    let signers2 = AnchoringSigners::from_bytes(bytes.into());
+    assert_eq!(signers, signers2);
+}
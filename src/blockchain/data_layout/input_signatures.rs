@@ -13,7 +13,6 @@
 // limitations under the License.
 
 use exonum::crypto::{self, CryptoHash, Hash};
-use exonum::helpers::ValidatorId;
 use exonum::storage::StorageValue;
 
 use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
@@ -24,31 +23,42 @@ use std::io::{Cursor, Write};
 use std::iter::{FilterMap, IntoIterator};
 use std::vec::IntoIter;
 
-/// A set of signatures for a transaction input ordered by the validators identifiers.
+use crate::config::SignerId;
+
+/// A set of signatures for a transaction input ordered by the signers identifiers.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InputSignatures {
     content: Vec<Option<Vec<u8>>>,
 }
 
 impl InputSignatures {
-    /// Creates an empty signatures set for the given validators count.
-    pub fn new(validators_count: usize) -> Self {
-        let content = vec![None; validators_count as usize];
+    /// Creates an empty signatures set for the given signers count.
+    pub fn new(signers_count: usize) -> Self {
+        let content = vec![None; signers_count as usize];
         Self { content }
     }
 
-    /// Inserts a signature from the validator with the given identifier.
-    pub fn insert(&mut self, id: ValidatorId, signature: Vec<u8>) {
+    /// Inserts a signature from the signer with the given identifier.
+    pub fn insert(&mut self, id: SignerId, signature: Vec<u8>) {
         let index = id.0 as usize;
         self.content[index] = Some(signature);
     }
 
-    /// Checks the existence of a signature from the validator with the given identifier.
-    pub fn contains(&self, id: ValidatorId) -> bool {
+    /// Checks the existence of a signature from the signer with the given identifier.
+    pub fn contains(&self, id: SignerId) -> bool {
         let index = id.0 as usize;
         self.content[index].is_some()
     }
 
+    /// Returns identifiers of the signers whose signatures are in the set.
+    pub fn signers(&self) -> impl Iterator<Item = SignerId> + '_ {
+        self.content
+            .iter()
+            .enumerate()
+            .filter(|(_, signature)| signature.is_some())
+            .map(|(index, _)| SignerId(index as u16))
+    }
+
     /// Returns the total count of signatures.
     pub fn len(&self) -> usize {
         self.content.iter().filter(|x| x.is_some()).count()
@@ -117,8 +127,8 @@ fn test_input_signatures_storage_value() {
         b"abacaba1224634abcfdfdfca353".to_vec(),
         b"abacaba1224634abcfdfdfca353ee2224774".to_vec(),
     ];
-    signatures.insert(ValidatorId(3), data[1].clone());
-    signatures.insert(ValidatorId(1), data[0].clone());
+    signatures.insert(SignerId(3), data[1].clone());
+    signatures.insert(SignerId(1), data[0].clone());
     assert_eq!(signatures.len(), 2);
 
     let bytes = signatures.clone().into_bytes();
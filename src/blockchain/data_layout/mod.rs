@@ -14,8 +14,16 @@
 
 //! Additional data types for the BTC anchoring information schema.
 
+pub use self::anchoring_request_votes::AnchoringRequestVotes;
+pub use self::anchoring_signers::AnchoringSigners;
 pub use self::input_signatures::InputSignatures;
+pub use self::signer_stats::SignerStats;
+pub use self::sweep_request::SweepRequest;
 pub use self::tx_input_id::TxInputId;
 
+mod anchoring_request_votes;
+mod anchoring_signers;
 mod input_signatures;
+mod signer_stats;
+mod sweep_request;
 mod tx_input_id;
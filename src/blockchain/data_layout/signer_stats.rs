@@ -0,0 +1,88 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum::crypto::{self, CryptoHash, Hash};
+use exonum::storage::StorageValue;
+
+use byteorder::{ByteOrder, LittleEndian};
+use serde_derive::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+
+/// Participation statistics of the anchoring signer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SignerStats {
+    /// Number of the finalized anchoring transactions signed by the signer.
+    pub signed: u64,
+    /// Number of the finalized anchoring transactions missed by the signer.
+    pub missed: u64,
+    /// Total latency in blocks between the proposal availability and the signature.
+    pub latency_sum: u64,
+    /// Number of the signatures for which the latency was measured.
+    pub latency_samples: u64,
+}
+
+impl SignerStats {
+    /// Returns the average latency in blocks between the proposal availability
+    /// and the signature.
+    pub fn average_latency(&self) -> Option<f64> {
+        if self.latency_samples == 0 {
+            None
+        } else {
+            Some(self.latency_sum as f64 / self.latency_samples as f64)
+        }
+    }
+}
+
+impl StorageValue for SignerStats {
+    fn into_bytes(self) -> Vec<u8> {
+        let mut buf = vec![0_u8; 32];
+        LittleEndian::write_u64(&mut buf[0..8], self.signed);
+        LittleEndian::write_u64(&mut buf[8..16], self.missed);
+        LittleEndian::write_u64(&mut buf[16..24], self.latency_sum);
+        LittleEndian::write_u64(&mut buf[24..32], self.latency_samples);
+        buf
+    }
+
+    fn from_bytes(value: Cow<[u8]>) -> Self {
+        let bytes = value.as_ref();
+        Self {
+            signed: LittleEndian::read_u64(&bytes[0..8]),
+            missed: LittleEndian::read_u64(&bytes[8..16]),
+            latency_sum: LittleEndian::read_u64(&bytes[16..24]),
+            latency_samples: LittleEndian::read_u64(&bytes[24..32]),
+        }
+    }
+}
+
+impl CryptoHash for SignerStats {
+    fn hash(&self) -> Hash {
+        crypto::hash(&self.into_bytes())
+    }
+}
+
+#[test]
+fn test_signer_stats_storage_value() {
+    let stats = SignerStats {
+        signed: 10,
+        missed: 2,
+        latency_sum: 15,
+        latency_samples: 10,
+    };
+    assert_eq!(stats.average_latency(), Some(1.5));
+    assert_eq!(SignerStats::default().average_latency(), None);
+
+    let bytes = stats.into_bytes();
+    assert_eq!(SignerStats::from_bytes(bytes.into()), stats);
+}
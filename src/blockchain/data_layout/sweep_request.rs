@@ -0,0 +1,77 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum::crypto::{self, CryptoHash, Hash};
+use exonum::helpers::Height;
+use exonum::storage::StorageValue;
+
+use byteorder::{ByteOrder, LittleEndian};
+use serde_derive::{Deserialize, Serialize};
+
+use std::borrow::Cow;
+
+use crate::btc::Transaction;
+
+/// Approved request to sweep the funds sent to the retired anchoring addresses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepRequest {
+    /// Transaction with the outputs to the retired anchoring addresses.
+    pub transaction: Transaction,
+    /// Height at which the request has been approved.
+    pub height: Height,
+}
+
+impl StorageValue for SweepRequest {
+    fn into_bytes(self) -> Vec<u8> {
+        let mut buf = vec![0_u8; 8];
+        LittleEndian::write_u64(&mut buf[0..8], self.height.0);
+        buf.extend(self.transaction.into_bytes());
+        buf
+    }
+
+    fn from_bytes(value: Cow<[u8]>) -> Self {
+        let bytes = value.as_ref();
+        Self {
+            height: Height(LittleEndian::read_u64(&bytes[0..8])),
+            transaction: Transaction::from_bytes(Cow::Borrowed(&bytes[8..])),
+        }
+    }
+}
+
+impl CryptoHash for SweepRequest {
+    fn hash(&self) -> Hash {
+        crypto::hash(&self.clone().into_bytes())
+    }
+}
+
+#[test]
+fn test_sweep_request_storage_value() {
+    let transaction = "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+                       dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+                       cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+                       b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+                       eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+                       e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+                       021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+                       062c1400"
+        .parse::<Transaction>()
+        .unwrap();
+    let request = SweepRequest {
+        transaction,
+        height: Height(10),
+    };
+
+    let bytes = request.clone().into_bytes();
+    assert_eq!(SweepRequest::from_bytes(bytes.into()), request);
+}
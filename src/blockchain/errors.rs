@@ -16,11 +16,26 @@
 
 use exonum::blockchain::ExecutionError;
 use exonum::crypto::Hash;
-use exonum::helpers::ValidatorId;
+use exonum::helpers::Height;
 
 use failure_derive::Fail;
 
 use crate::btc;
+use crate::config::{ConfigErrors, SignerId};
+
+/// Reasons why the anchoring configuration cannot be used.
+#[derive(Debug, Fail)]
+pub enum ConfigurationError {
+    /// The anchoring configuration is absent in the stored configuration.
+    #[fail(display = "BTC anchoring configuration is absent.")]
+    Absent,
+    /// The anchoring configuration cannot be deserialized.
+    #[fail(display = "Unable to parse BTC anchoring configuration: {}", _0)]
+    Malformed(serde_json::Error),
+    /// The anchoring configuration doesn't pass the validation.
+    #[fail(display = "{}", _0)]
+    Invalid(ConfigErrors),
+}
 
 /// Possible errors during execution of the `Signature` transaction.
 #[derive(Debug, Fail)]
@@ -41,11 +56,11 @@ pub enum SignatureError {
         display = "Received signature for anchoring transaction while the node is in transition state."
     )]
     InTransition,
-    /// Public key of validator with the given identifier is missing.
-    #[fail(display = "Public key of validator {} is missing.", _0)]
+    /// Public key of signer with the given identifier is missing.
+    #[fail(display = "Public key of signer {} is missing.", signer_id)]
     MissingPublicKey {
-        /// Validator identifier.
-        validator_id: ValidatorId,
+        /// Signer identifier.
+        signer_id: SignerId,
     },
     /// Input with the given index does not exist.
     #[fail(display = "Input with index {} does not exist.", _0)]
@@ -59,11 +74,71 @@ pub enum SignatureError {
     /// An error in transaction builder occurred.
     #[fail(display = "{}", _0)]
     TxBuilderError(btc::BuilderError),
+    /// Transaction author is not the signer with the given identifier.
+    #[fail(display = "Transaction author is not the signer {}.", signer_id)]
+    UnauthorizedSigner {
+        /// Signer identifier.
+        signer_id: SignerId,
+    },
+    /// Transaction author is not the validator with the given identifier.
+    #[fail(display = "Transaction author is not the validator {}.", signer_id)]
+    ValidatorMismatch {
+        /// Claimed validator identifier.
+        signer_id: SignerId,
+    },
+    /// The number of signatures doesn't match the number of inputs.
+    #[fail(
+        display = "Expected signatures for {} inputs, but received {}.",
+        expected, actual
+    )]
+    InputsCountMismatch {
+        /// Expected number of signatures.
+        expected: usize,
+        /// Actually received number of signatures.
+        actual: usize,
+    },
+    /// Anchoring is suspended since the anchoring service is misconfigured.
+    #[fail(display = "Anchoring is suspended since the service is misconfigured.")]
+    Misconfigured,
+    /// Received signature while the anchoring is paused.
+    #[fail(display = "Received signature while the anchoring is paused.")]
+    Paused,
+    /// Received signature for the sweep transaction while there are no approved sweep requests.
+    #[fail(
+        display = "Received signature for the sweep transaction while there are no approved sweep requests."
+    )]
+    NoSweepProposal,
+    /// Transaction author is not a signer of the retired anchoring address spent by the input.
+    #[fail(
+        display = "Transaction author is not a signer of the retired anchoring address spent by the input."
+    )]
+    UnauthorizedSweepSigner,
     /// An unknown error occurred.
     #[fail(display = "Unknown error")]
     UnknownError,
 }
 
+/// Possible errors during execution of the `AnchoringRequest` transaction.
+#[derive(Debug, Fail)]
+pub enum AnchoringRequestError {
+    /// Transaction author is neither a validator nor the anchoring administrator.
+    /// Sweep requests are accepted from the validators only.
+    #[fail(display = "Transaction author is neither a validator nor the anchoring administrator.")]
+    UnauthorizedRequest,
+    /// The requested block is already anchored or doesn't exist yet.
+    #[fail(
+        display = "Block at height {} is already anchored or doesn't exist yet.",
+        _0
+    )]
+    IncorrectHeight(Height),
+    /// The transaction has no unspent outputs to the retired anchoring addresses.
+    #[fail(display = "Transaction has no unspent outputs to the retired anchoring addresses.")]
+    UnsuitableSweepTransaction,
+    /// The request is received while the anchoring service is misconfigured.
+    #[fail(display = "Anchoring is suspended since the service is misconfigured.")]
+    Misconfigured,
+}
+
 /// Error codes for the BTC anchoring transactions.
 #[derive(Debug)]
 pub enum ErrorCode {
@@ -79,6 +154,26 @@ pub enum ErrorCode {
     VerificationFailed = 5,
     /// [description](SignatureError.t.html#variant.TxBuilderError)
     TxBuilderError = 6,
+    /// [description](SignatureError.t.html#variant.UnauthorizedSigner)
+    UnauthorizedSigner = 7,
+    /// [description](SignatureError.t.html#variant.ValidatorMismatch)
+    ValidatorMismatch = 8,
+    /// [description](SignatureError.t.html#variant.InputsCountMismatch)
+    InputsCountMismatch = 9,
+    /// [description](SignatureError.t.html#variant.Misconfigured)
+    Misconfigured = 10,
+    /// [description](SignatureError.t.html#variant.Paused)
+    Paused = 11,
+    /// [description](AnchoringRequestError.t.html#variant.UnauthorizedRequest)
+    UnauthorizedRequest = 12,
+    /// [description](AnchoringRequestError.t.html#variant.IncorrectHeight)
+    IncorrectHeight = 13,
+    /// [description](AnchoringRequestError.t.html#variant.UnsuitableSweepTransaction)
+    UnsuitableSweepTransaction = 14,
+    /// [description](SignatureError.t.html#variant.NoSweepProposal)
+    NoSweepProposal = 15,
+    /// [description](SignatureError.t.html#variant.UnauthorizedSweepSigner)
+    UnauthorizedSweepSigner = 16,
     /// [description](SignatureError.t.html#variant.UnknownError)
     UnknownError = 255,
 }
@@ -92,6 +187,13 @@ impl SignatureError {
             SignatureError::NoSuchInput { .. } => ErrorCode::NoSuchInput,
             SignatureError::VerificationFailed => ErrorCode::VerificationFailed,
             SignatureError::TxBuilderError(..) => ErrorCode::TxBuilderError,
+            SignatureError::UnauthorizedSigner { .. } => ErrorCode::UnauthorizedSigner,
+            SignatureError::ValidatorMismatch { .. } => ErrorCode::ValidatorMismatch,
+            SignatureError::InputsCountMismatch { .. } => ErrorCode::InputsCountMismatch,
+            SignatureError::Misconfigured => ErrorCode::Misconfigured,
+            SignatureError::Paused => ErrorCode::Paused,
+            SignatureError::NoSweepProposal => ErrorCode::NoSweepProposal,
+            SignatureError::UnauthorizedSweepSigner => ErrorCode::UnauthorizedSweepSigner,
             _ => ErrorCode::UnknownError,
         }
     }
@@ -103,3 +205,23 @@ impl From<SignatureError> for ExecutionError {
         Self::with_description(value.code() as u8, description)
     }
 }
+
+impl AnchoringRequestError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            AnchoringRequestError::UnauthorizedRequest => ErrorCode::UnauthorizedRequest,
+            AnchoringRequestError::IncorrectHeight(..) => ErrorCode::IncorrectHeight,
+            AnchoringRequestError::UnsuitableSweepTransaction => {
+                ErrorCode::UnsuitableSweepTransaction
+            }
+            AnchoringRequestError::Misconfigured => ErrorCode::Misconfigured,
+        }
+    }
+}
+
+impl From<AnchoringRequestError> for ExecutionError {
+    fn from(value: AnchoringRequestError) -> Self {
+        let description = format!("{}", value);
+        Self::with_description(value.code() as u8, description)
+    }
+}
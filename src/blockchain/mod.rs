@@ -17,20 +17,29 @@
 pub use self::schema::BtcAnchoringSchema;
 pub use self::transactions::Transactions;
 
+use exonum::crypto;
 use exonum::helpers::Height;
 
 use bitcoin::blockdata::script::Script;
 use btc_transaction_utils::multisig::RedeemScript;
 use btc_transaction_utils::p2wsh;
 
-use crate::btc::Address;
-use crate::config::GlobalConfig;
+use std::cmp;
+
+use crate::btc::{Address, Transaction};
+use crate::config::{CatchUpPolicy, GlobalConfig, SignerId};
 
 pub mod data_layout;
 pub mod errors;
 pub mod schema;
 pub mod transactions;
 
+/// Number of blocks after the approval during which the sweep request should be
+/// fulfilled, otherwise it expires. Thus the request for the funds which cannot be
+/// swept, for example, because the retired signers are unable to sign the sweep
+/// transaction, doesn't hold up the following requests forever.
+pub const SWEEP_REQUEST_LIFETIME: u64 = 1_000;
+
 /// Current state of the BTC anchoring service.
 #[derive(Debug, Clone)]
 pub enum BtcAnchoringState {
@@ -48,6 +57,38 @@ pub enum BtcAnchoringState {
     },
 }
 
+/// Anchoring configuration which is no longer actual. Its redeem script is used to sweep
+/// the funds sent to the retired anchoring address.
+#[derive(Debug, Clone)]
+pub struct RetiredConfiguration {
+    /// Retired anchoring configuration.
+    pub configuration: GlobalConfig,
+    /// Service keys of the validators from the consensus configuration in which
+    /// the anchoring configuration was actual.
+    pub validator_keys: Vec<crypto::PublicKey>,
+}
+
+impl RetiredConfiguration {
+    /// Returns the script_pubkey of the retired anchoring address.
+    pub fn script_pubkey(&self) -> Script {
+        self.configuration.redeem_script().as_ref().to_v0_p2wsh()
+    }
+
+    /// Returns the identifier of the anchoring signer with the given service key
+    /// in the retired configuration.
+    pub fn signer_id(&self, service_key: &crypto::PublicKey) -> Option<SignerId> {
+        if self.configuration.signers.is_empty() {
+            // In the legacy mode signers are validators.
+            self.validator_keys
+                .iter()
+                .position(|key| key == service_key)
+                .map(|id| SignerId(id as u16))
+        } else {
+            self.configuration.signer_id(service_key)
+        }
+    }
+}
+
 impl BtcAnchoringState {
     /// Returns the redeem script which corresponds to the address to which the anchoring
     /// transaction will be sent.
@@ -115,13 +156,56 @@ impl BtcAnchoringState {
         }
     }
 
-    /// Returns the nearest following anchoring height for the given height.
-    pub fn following_anchoring_height(&self, latest_anchored_height: Option<Height>) -> Height {
-        latest_anchored_height.map_or_else(Height::zero, |height| match self {
+    /// Returns the redeem script of the output spent by the given input of the anchoring
+    /// transaction. In the transition state, the transition transaction may spend the funding
+    /// transaction sent to the following address in addition to the actual anchoring output.
+    pub fn input_redeem_script(
+        &self,
+        transaction: &Transaction,
+        input: usize,
+        input_transaction: &Transaction,
+    ) -> RedeemScript {
+        let vout = transaction.0.input[input].previous_output.vout as usize;
+        let script_pubkey = &input_transaction.0.output[vout].script_pubkey;
+        match self.following_configuration() {
+            Some(config) if config.redeem_script().as_ref().to_v0_p2wsh() == *script_pubkey => {
+                config.redeem_script()
+            }
+            _ => self.actual_configuration().redeem_script(),
+        }
+    }
+
+    /// Returns the height which should be anchored next after the given latest anchored
+    /// height at the given current blockchain height.
+    ///
+    /// If the catch-up policy of the actual configuration is `LatestHeight`, the anchoring
+    /// jumps directly to the latest height below the current one which must be anchored.
+    /// The skipped blocks are still covered by the hash linkage of the anchored blocks.
+    pub fn following_anchoring_height(
+        &self,
+        latest_anchored_height: Option<Height>,
+        current_height: Height,
+    ) -> Height {
+        match self {
             BtcAnchoringState::Regular {
                 ref actual_configuration,
-            } => actual_configuration.following_anchoring_height(height),
-            BtcAnchoringState::Transition { .. } => height,
-        })
+            } => {
+                let following_height = latest_anchored_height.map_or_else(Height::zero, |height| {
+                    actual_configuration.following_anchoring_height(height)
+                });
+                if actual_configuration.catch_up_policy == CatchUpPolicy::LatestHeight
+                    && current_height > Height::zero()
+                {
+                    let latest_height =
+                        actual_configuration.previous_anchoring_height(current_height.previous());
+                    cmp::max(following_height, latest_height)
+                } else {
+                    following_height
+                }
+            }
+            BtcAnchoringState::Transition { .. } => {
+                latest_anchored_height.unwrap_or_else(Height::zero)
+            }
+        }
     }
 }
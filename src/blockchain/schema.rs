@@ -15,20 +15,24 @@
 //! Information schema for the btc anchoring service.
 
 use exonum::blockchain::{Schema, StoredConfiguration};
-use exonum::crypto::Hash;
+use exonum::crypto::{self, Hash};
 use exonum::helpers::Height;
-use exonum::storage::{Fork, ProofListIndex, ProofMapIndex, Snapshot};
+use exonum::storage::{Fork, MapIndex, ProofListIndex, ProofMapIndex, Snapshot};
 
+use bitcoin::blockdata::script::Script;
 use btc_transaction_utils::multisig::RedeemScript;
-use log::{error, trace};
+use log::{error, trace, warn};
 use serde_json;
 
-use crate::btc::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
-use crate::config::GlobalConfig;
+use crate::btc::{
+    self, BtcAnchoringTransactionBuilder, BtcSweepTransactionBuilder, BuilderError, Transaction,
+};
+use crate::config::{GlobalConfig, SignerId};
 use crate::BTC_ANCHORING_SERVICE_NAME;
 
 use super::data_layout::*;
-use super::BtcAnchoringState;
+use super::errors::ConfigurationError;
+use super::{BtcAnchoringState, RetiredConfiguration, SWEEP_REQUEST_LIFETIME};
 
 /// Defines `&str` constants with given name and value.
 macro_rules! define_names {
@@ -46,6 +50,15 @@ define_names!(
     TRANSACTION_SIGNATURES => "transaction_signatures";
     SPENT_FUNDING_TRANSACTIONS => "spent_funding_transactions";
     ANCHORED_BLOCKS => "anchored_blocks";
+    ANCHORING_SIGNERS => "anchoring_signers";
+    SIGNERS_STATS => "signers_stats";
+    PROPOSAL_HEIGHTS => "proposal_heights";
+    ANCHORING_REQUEST_VOTES => "anchoring_request_votes";
+    ANCHORING_REQUESTS => "anchoring_requests";
+    SWEEP_REQUEST_VOTES => "sweep_request_votes";
+    SWEEP_REQUESTS => "sweep_requests";
+    SWEEP_SIGNATURES => "sweep_signatures";
+    SWEEP_TRANSACTIONS => "sweep_transactions";
 );
 
 /// Information schema for `exonum-btc-anchoring`.
@@ -80,6 +93,60 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
         ProofListIndex::new(ANCHORED_BLOCKS, &self.snapshot)
     }
 
+    /// Returns the table that contains signers of the finalized anchoring transactions.
+    pub fn anchoring_signers(&self) -> ProofMapIndex<&T, Hash, AnchoringSigners> {
+        ProofMapIndex::new(ANCHORING_SIGNERS, &self.snapshot)
+    }
+
+    /// Returns the table that contains participation statistics for each Bitcoin public key
+    /// of the anchoring signers, so the statistics are not mixed up when the keys are changed.
+    pub fn signers_stats(&self) -> MapIndex<&T, btc::PublicKey, SignerStats> {
+        MapIndex::new(SIGNERS_STATS, &self.snapshot)
+    }
+
+    /// Returns the table that contains the heights at which the anchoring proposals
+    /// became available.
+    pub fn proposal_heights(&self) -> MapIndex<&T, Hash, u64> {
+        MapIndex::new(PROPOSAL_HEIGHTS, &self.snapshot)
+    }
+
+    /// Returns the table that contains the votes of the validators for the out-of-schedule
+    /// anchoring of the block with the given hash.
+    pub fn anchoring_request_votes(&self) -> ProofMapIndex<&T, Hash, AnchoringRequestVotes> {
+        ProofMapIndex::new(ANCHORING_REQUEST_VOTES, &self.snapshot)
+    }
+
+    /// Returns the table that contains the heights of the approved blocks which should be
+    /// anchored out of schedule, indexed by the block hashes.
+    pub fn anchoring_requests(&self) -> ProofMapIndex<&T, Hash, u64> {
+        ProofMapIndex::new(ANCHORING_REQUESTS, &self.snapshot)
+    }
+
+    /// Returns the table that contains the votes of the validators for sweeping the funds
+    /// of the transaction with the given identifier.
+    pub fn sweep_request_votes(&self) -> ProofMapIndex<&T, Hash, AnchoringRequestVotes> {
+        ProofMapIndex::new(SWEEP_REQUEST_VOTES, &self.snapshot)
+    }
+
+    /// Returns the table that contains the approved requests to sweep the funds of the
+    /// transactions with the outputs to the retired anchoring addresses, indexed by the
+    /// identifiers of these transactions.
+    pub fn sweep_requests(&self) -> ProofMapIndex<&T, Hash, SweepRequest> {
+        ProofMapIndex::new(SWEEP_REQUESTS, &self.snapshot)
+    }
+
+    /// Returns the table that contains signatures for the given input of the proposed
+    /// sweep transaction.
+    pub fn sweep_signatures(&self) -> ProofMapIndex<&T, TxInputId, InputSignatures> {
+        ProofMapIndex::new(SWEEP_SIGNATURES, &self.snapshot)
+    }
+
+    /// Returns the table that contains the finalized sweep transactions, which should be
+    /// sent to the Bitcoin network.
+    pub fn sweep_transactions(&self) -> ProofMapIndex<&T, Hash, Transaction> {
+        ProofMapIndex::new(SWEEP_TRANSACTIONS, &self.snapshot)
+    }
+
     /// Returns hashes of the stored tables.
     pub fn state_hash(&self) -> Vec<Hash> {
         vec![
@@ -87,20 +154,215 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
             self.spent_funding_transactions().merkle_root(),
             self.transaction_signatures().merkle_root(),
             self.anchored_blocks().merkle_root(),
+            self.anchoring_signers().merkle_root(),
+            self.anchoring_request_votes().merkle_root(),
+            self.anchoring_requests().merkle_root(),
+            self.sweep_request_votes().merkle_root(),
+            self.sweep_requests().merkle_root(),
+            self.sweep_signatures().merkle_root(),
+            self.sweep_transactions().merkle_root(),
         ]
     }
 
+    /// Returns the actual anchoring configuration or an error if the anchoring service
+    /// is misconfigured, that is, the actual anchoring configuration is absent or malformed.
+    ///
+    /// Anchoring configurations which do not pass the validation are ignored, thus the latest
+    /// valid one is used instead. If the latest valid configuration identifies the signers by
+    /// the validator identifiers, it remains bound to the validators it was actual with.
+    pub fn try_actual_configuration(&self) -> Result<GlobalConfig, ConfigurationError> {
+        let schema = Schema::new(&self.snapshot);
+        let actual_configuration = schema.actual_configuration();
+        let error = match Self::parse_config(&actual_configuration) {
+            Ok(config) => return Ok(config),
+            Err(e @ ConfigurationError::Invalid(_)) => e,
+            Err(e) => return Err(e),
+        };
+        warn!(
+            "Ignoring BTC anchoring configuration actual from {}: {}",
+            actual_configuration.actual_from, error
+        );
+
+        let configs = schema.configs();
+        schema
+            .configs_actual_from()
+            .iter()
+            .rev()
+            .filter(|reference| reference.actual_from() < actual_configuration.actual_from)
+            .filter_map(|reference| configs.get(reference.cfg_hash()))
+            .find_map(|configuration| {
+                let mut config = Self::parse_config(&configuration).ok()?;
+                // The validator set may be changed along with the invalid configuration,
+                // so the legacy signers are replaced by the explicit ones.
+                if config.signers.is_empty()
+                    && configuration.validator_keys.len() >= config.public_keys.len()
+                {
+                    config.signers = configuration
+                        .validator_keys
+                        .iter()
+                        .take(config.public_keys.len())
+                        .map(|keys| keys.service_key)
+                        .collect();
+                }
+                Some(config)
+            })
+            .ok_or(error)
+    }
+
     /// Returns the actual anchoring configuration.
+    ///
+    /// # Panics
+    ///
+    /// If the anchoring service is misconfigured.
     pub fn actual_configuration(&self) -> GlobalConfig {
-        let actual_configuration = Schema::new(&self.snapshot).actual_configuration();
-        Self::parse_config(&actual_configuration)
-            .expect("Actual BTC anchoring configuration is absent")
+        self.try_actual_configuration()
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
-    /// Returns the nearest following configuration if it exists.
+    /// Returns the nearest following configuration if it exists and is valid.
     pub fn following_configuration(&self) -> Option<GlobalConfig> {
         let following_configuration = Schema::new(&self.snapshot).following_configuration()?;
         Self::parse_config(&following_configuration)
+            .map_err(|e| {
+                warn!(
+                    "Ignoring BTC anchoring configuration actual from {}: {}",
+                    following_configuration.actual_from, e
+                )
+            })
+            .ok()
+    }
+
+    /// Returns the anchoring configurations which were actual before the actual one and
+    /// have the different anchoring addresses. Exonum keeps all consensus configurations,
+    /// so the redeem scripts of the retired anchoring addresses are always available.
+    pub fn retired_configurations(&self) -> Vec<RetiredConfiguration> {
+        let actual_script_pubkey = match self.try_actual_configuration() {
+            Ok(config) => config.redeem_script().as_ref().to_v0_p2wsh(),
+            Err(_) => return Vec::new(),
+        };
+        let schema = Schema::new(&self.snapshot);
+        let actual_from = schema.actual_configuration().actual_from;
+        let configs = schema.configs();
+
+        let mut retired_configurations: Vec<RetiredConfiguration> = Vec::new();
+        let stored_configurations = schema
+            .configs_actual_from()
+            .iter()
+            .filter(|reference| reference.actual_from() < actual_from)
+            .filter_map(|reference| configs.get(reference.cfg_hash()));
+        for stored_configuration in stored_configurations {
+            let configuration = match Self::parse_config(&stored_configuration) {
+                Ok(configuration) => configuration,
+                Err(_) => continue,
+            };
+            let retired_configuration = RetiredConfiguration {
+                configuration,
+                validator_keys: stored_configuration
+                    .validator_keys
+                    .iter()
+                    .map(|keys| keys.service_key)
+                    .collect(),
+            };
+            let script_pubkey = retired_configuration.script_pubkey();
+            let is_known = script_pubkey == actual_script_pubkey
+                || retired_configurations
+                    .iter()
+                    .any(|retired| retired.script_pubkey() == script_pubkey);
+            if !is_known {
+                retired_configurations.push(retired_configuration);
+            }
+        }
+        retired_configurations
+    }
+
+    /// Returns the retired anchoring configuration with the given anchoring script_pubkey.
+    pub fn retired_configuration(&self, script_pubkey: &Script) -> Option<RetiredConfiguration> {
+        self.retired_configurations()
+            .into_iter()
+            .find(|retired| retired.script_pubkey() == *script_pubkey)
+    }
+
+    /// Returns the proposal of the transaction which sweeps the funds of the earliest
+    /// approved sweep request into the actual anchoring address.
+    ///
+    /// Sweep transactions are signed separately from the anchoring ones by the signers
+    /// of the retired configurations, so sweeping never blocks the anchoring.
+    pub fn proposed_sweep_transaction(
+        &self,
+    ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
+        let config = self.try_actual_configuration().ok()?;
+        let request = self
+            .sweep_requests()
+            .values()
+            .min_by_key(|request| request.height)?;
+
+        let mut builder = BtcSweepTransactionBuilder::new(&config.redeem_script());
+        for retired in self.retired_configurations() {
+            let script_pubkey = retired.script_pubkey();
+            if request.transaction.find_out(&script_pubkey).is_some() {
+                if let Err(e) = builder.retired_funds(request.transaction.clone(), &script_pubkey) {
+                    return Some(Err(e));
+                }
+            }
+        }
+        builder.fee(config.transaction_fee);
+        Some(builder.create())
+    }
+
+    /// Returns the retired configuration of the address spent by the given input
+    /// of the sweep transaction.
+    pub fn sweep_input_configuration(
+        &self,
+        transaction: &Transaction,
+        input: usize,
+        input_transaction: &Transaction,
+    ) -> Option<RetiredConfiguration> {
+        let vout = transaction.0.input[input].previous_output.vout as usize;
+        let script_pubkey = &input_transaction.0.output[vout].script_pubkey;
+        self.retired_configuration(script_pubkey)
+    }
+
+    /// Returns the redeem script of the output spent by the given input of the anchoring
+    /// transaction along with the identifier of the given anchoring signer in it.
+    ///
+    /// Inputs which spend the following anchoring address are signed by the anchoring
+    /// signers of the following configuration, which are identified by their service keys.
+    /// For the other inputs the signer is identified by its actual anchoring public key.
+    pub fn input_signer(
+        &self,
+        actual_state: &BtcAnchoringState,
+        transaction: &Transaction,
+        input: usize,
+        input_transaction: &Transaction,
+        signer: SignerId,
+        service_key: &crypto::PublicKey,
+    ) -> (RedeemScript, Option<SignerId>) {
+        let redeem_script = actual_state.input_redeem_script(transaction, input, input_transaction);
+        let is_following =
+            redeem_script.as_ref() != actual_state.actual_configuration().redeem_script().as_ref();
+        if is_following {
+            let signer_id = self
+                .following_signers(actual_state)
+                .and_then(|signers| signers.iter().position(|key| key == service_key))
+                .map(|id| SignerId(id as u16));
+            return (redeem_script, signer_id);
+        }
+
+        let signer_id = actual_state
+            .actual_configuration()
+            .redeem_script()
+            .content()
+            .public_keys
+            .get(signer.0 as usize)
+            .and_then(|public_key| {
+                redeem_script
+                    .content()
+                    .public_keys
+                    .iter()
+                    .position(|pk| pk == public_key)
+            })
+            .map(|id| SignerId(id as u16));
+        (redeem_script, signer_id)
     }
 
     /// Returns the list of signatures for the given transaction input.
@@ -114,29 +376,95 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
             .unwrap_or_else(|| InputSignatures::new(redeem_script.content().public_keys.len()))
     }
 
-    /// Returns the actual state of anchoring.
-    pub fn actual_state(&self) -> BtcAnchoringState {
-        let actual_configuration = self.actual_configuration();
+    /// Returns the actual state of anchoring or an error if the anchoring service
+    /// is misconfigured.
+    pub fn try_actual_state(&self) -> Result<BtcAnchoringState, ConfigurationError> {
+        let actual_configuration = self.try_actual_configuration()?;
         if let Some(following_configuration) = self.following_configuration() {
             if actual_configuration.redeem_script() != following_configuration.redeem_script() {
-                return BtcAnchoringState::Transition {
+                return Ok(BtcAnchoringState::Transition {
                     actual_configuration,
                     following_configuration,
-                };
+                });
             }
         }
 
-        BtcAnchoringState::Regular {
+        Ok(BtcAnchoringState::Regular {
             actual_configuration,
+        })
+    }
+
+    /// Returns the actual state of anchoring.
+    ///
+    /// # Panics
+    ///
+    /// If the anchoring service is misconfigured.
+    pub fn actual_state(&self) -> BtcAnchoringState {
+        self.try_actual_state().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Returns the height which should be anchored next for the given anchoring state.
+    /// The earliest approved anchoring request takes precedence over the schedule.
+    pub fn following_anchoring_height(&self, actual_state: &BtcAnchoringState) -> Height {
+        let current_height = Height(Schema::new(&self.snapshot).block_hashes_by_height().len());
+        let latest_anchored_height = self.latest_anchored_height();
+        let scheduled_height =
+            actual_state.following_anchoring_height(latest_anchored_height, current_height);
+        if !actual_state.is_regular() {
+            return scheduled_height;
         }
+
+        self.anchoring_requests()
+            .values()
+            .map(Height)
+            .filter(|height| latest_anchored_height.map_or(true, |latest| *height > latest))
+            .min()
+            .filter(|height| *height < scheduled_height)
+            .unwrap_or(scheduled_height)
     }
 
     /// Returns the proposal of next anchoring transaction for the given anchoring state.
+    /// There is no proposal while the anchoring is paused.
+    ///
+    /// If the funds of the actual anchoring address are insufficient to pay the fee of
+    /// the transition transaction, the unspent funding transaction of the following
+    /// configuration is spent by the transition transaction as well.
     pub fn proposed_anchoring_transaction(
         &self,
         actual_state: &BtcAnchoringState,
     ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
         let config = actual_state.actual_configuration();
+        let core_schema = Schema::new(&self.snapshot);
+        let current_height = Height(core_schema.block_hashes_by_height().len());
+        if config.is_paused(current_height) {
+            trace!("Anchoring is paused.");
+            return None;
+        }
+
+        let proposal = self.build_anchoring_transaction(actual_state, None);
+        if let Some(Err(BuilderError::InsufficientFunds { .. })) = proposal {
+            if let Some(tx) = self.unspent_following_funding_transaction(actual_state) {
+                if self.is_following_quorum_reachable(actual_state) {
+                    trace!("Spending the following funding transaction {}.", tx.id());
+                    return self.build_anchoring_transaction(actual_state, Some(tx));
+                }
+                warn!(
+                    "The following funding transaction {} cannot be spent by the actual signers.",
+                    tx.id()
+                );
+            }
+        }
+        proposal
+    }
+
+    fn build_anchoring_transaction(
+        &self,
+        actual_state: &BtcAnchoringState,
+        following_funding_transaction: Option<Transaction>,
+    ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
+        let config = actual_state.actual_configuration();
+        let core_schema = Schema::new(&self.snapshot);
+
         let unspent_anchoring_transaction = self.anchoring_transactions_chain().last();
         let unspent_funding_transaction = self.unspent_funding_transaction();
 
@@ -159,6 +487,11 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
                         actual_state.output_address(),
                     );
                     builder.transit_to(actual_state.script_pubkey());
+                    if let Some(tx) = following_funding_transaction {
+                        if let Err(e) = builder.transition_funds(tx) {
+                            return Some(Err(e));
+                        }
+                    }
                 }
             }
 
@@ -176,15 +509,23 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
                 return Some(Err(e));
             }
         }
+        // Swept funds of the retired addresses are spent as the additional ones.
+        for tx in self.unspent_sweep_transactions() {
+            if let Err(e) = builder.additional_funds(tx) {
+                return Some(Err(e));
+            }
+        }
 
         // Adds corresponding payload.
-        let latest_anchored_height = self.latest_anchored_height();
-        let anchoring_height = actual_state.following_anchoring_height(latest_anchored_height);
+        let anchoring_height = self.following_anchoring_height(actual_state);
 
-        let anchoring_block_hash =
-            Schema::new(&self.snapshot).block_hash_by_height(anchoring_height)?;
+        let anchoring_block_hash = core_schema.block_hash_by_height(anchoring_height)?;
 
         builder.payload(anchoring_height, anchoring_block_hash);
+        if config.anchor_state_hash {
+            let block = core_schema.blocks().get(&anchoring_block_hash)?;
+            builder.state_hash(*block.state_hash());
+        }
         builder.fee(config.transaction_fee);
 
         // Creates anchoring proposal.
@@ -192,16 +533,17 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
     }
 
     /// Returns the proposal of next anchoring transaction for the actual anchoring state.
+    /// There is no proposal if the anchoring service is misconfigured.
     pub fn actual_proposed_anchoring_transaction(
         &self,
     ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
-        let actual_state = self.actual_state();
+        let actual_state = self.try_actual_state().ok()?;
         self.proposed_anchoring_transaction(&actual_state)
     }
 
     /// Returns the unspent funding transaction if it is exist.
     pub fn unspent_funding_transaction(&self) -> Option<Transaction> {
-        let tx_candidate = self.actual_configuration().funding_transaction?;
+        let tx_candidate = self.try_actual_configuration().ok()?.funding_transaction?;
         let txid = tx_candidate.id();
         if self.spent_funding_transactions().contains(&txid) {
             None
@@ -210,6 +552,85 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
         }
     }
 
+    /// Returns the finalized sweep transactions which pay to the actual anchoring address
+    /// and are not spent by the anchoring transactions yet.
+    pub fn unspent_sweep_transactions(&self) -> Vec<Transaction> {
+        let actual_script_pubkey = match self.try_actual_configuration() {
+            Ok(config) => config.redeem_script().as_ref().to_v0_p2wsh(),
+            Err(_) => return Vec::new(),
+        };
+        let spent_funding_transactions = self.spent_funding_transactions();
+        self.sweep_transactions()
+            .values()
+            .filter(|tx| {
+                !spent_funding_transactions.contains(&tx.id())
+                    && tx.find_out(&actual_script_pubkey).is_some()
+            })
+            .collect()
+    }
+
+    /// Returns the unspent funding transaction of the following configuration if the anchoring
+    /// is in the transition state and there is such a transaction.
+    pub fn unspent_following_funding_transaction(
+        &self,
+        actual_state: &BtcAnchoringState,
+    ) -> Option<Transaction> {
+        let tx_candidate = actual_state
+            .following_configuration()?
+            .funding_transaction
+            .clone()?;
+        if self
+            .spent_funding_transactions()
+            .contains(&tx_candidate.id())
+        {
+            None
+        } else {
+            Some(tx_candidate)
+        }
+    }
+
+    /// Returns the Exonum service keys of the anchoring signers of the actual configuration.
+    pub fn actual_signers(&self, actual_state: &BtcAnchoringState) -> Vec<crypto::PublicKey> {
+        let consensus_configuration = Schema::new(&self.snapshot).actual_configuration();
+        signer_service_keys(
+            actual_state.actual_configuration(),
+            &consensus_configuration,
+        )
+    }
+
+    /// Returns the Exonum service keys of the anchoring signers of the following
+    /// configuration if the anchoring is in the transition state.
+    pub fn following_signers(
+        &self,
+        actual_state: &BtcAnchoringState,
+    ) -> Option<Vec<crypto::PublicKey>> {
+        let following_configuration = actual_state.following_configuration()?;
+        let consensus_configuration = Schema::new(&self.snapshot).following_configuration()?;
+        Some(signer_service_keys(
+            following_configuration,
+            &consensus_configuration,
+        ))
+    }
+
+    /// Checks that the outputs to the following anchoring address can be spent before the
+    /// following configuration becomes actual, that is, the actual anchoring signers
+    /// are enough to reach the quorum of the following configuration.
+    pub fn is_following_quorum_reachable(&self, actual_state: &BtcAnchoringState) -> bool {
+        let (following_configuration, following_signers) = match (
+            actual_state.following_configuration(),
+            self.following_signers(actual_state),
+        ) {
+            (Some(configuration), Some(signers)) => (configuration, signers),
+            _ => return false,
+        };
+        let actual_signers = self.actual_signers(actual_state);
+        let reachable_signers = following_signers
+            .iter()
+            .filter(|service_key| actual_signers.contains(service_key))
+            .count();
+        reachable_signers >= following_configuration.quorum()
+    }
+
     /// Returns the height of the latest anchored block.
     pub fn latest_anchored_height(&self) -> Option<Height> {
         let tx = self.anchoring_transactions_chain().last()?;
@@ -221,12 +642,17 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
         )
     }
 
-    fn parse_config(configuration: &StoredConfiguration) -> Option<GlobalConfig> {
-        configuration
+    fn parse_config(
+        configuration: &StoredConfiguration,
+    ) -> Result<GlobalConfig, ConfigurationError> {
+        let value = configuration
             .services
             .get(BTC_ANCHORING_SERVICE_NAME)
-            .cloned()
-            .map(|value| serde_json::from_value(value).expect("Unable to parse configuration"))
+            .ok_or(ConfigurationError::Absent)?;
+        let config = serde_json::from_value::<GlobalConfig>(value.clone())
+            .map_err(ConfigurationError::Malformed)?;
+        config.validate().map_err(ConfigurationError::Invalid)?;
+        Ok(config)
     }
 }
 
@@ -262,4 +688,208 @@ impl<'a> BtcAnchoringSchema<&'a mut Fork> {
     pub fn anchored_blocks_mut(&mut self) -> ProofListIndex<&mut Fork, Hash> {
         ProofListIndex::new(ANCHORED_BLOCKS, &mut self.snapshot)
     }
+
+    /// Mutable variant of the [`anchoring_signers`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.anchoring_signers
+    pub fn anchoring_signers_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, AnchoringSigners> {
+        ProofMapIndex::new(ANCHORING_SIGNERS, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`signers_stats`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.signers_stats
+    pub fn signers_stats_mut(&mut self) -> MapIndex<&mut Fork, btc::PublicKey, SignerStats> {
+        MapIndex::new(SIGNERS_STATS, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`anchoring_request_votes`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.anchoring_request_votes
+    pub fn anchoring_request_votes_mut(
+        &mut self,
+    ) -> ProofMapIndex<&mut Fork, Hash, AnchoringRequestVotes> {
+        ProofMapIndex::new(ANCHORING_REQUEST_VOTES, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`anchoring_requests`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.anchoring_requests
+    pub fn anchoring_requests_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, u64> {
+        ProofMapIndex::new(ANCHORING_REQUESTS, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`sweep_request_votes`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.sweep_request_votes
+    pub fn sweep_request_votes_mut(
+        &mut self,
+    ) -> ProofMapIndex<&mut Fork, Hash, AnchoringRequestVotes> {
+        ProofMapIndex::new(SWEEP_REQUEST_VOTES, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`sweep_requests`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.sweep_requests
+    pub fn sweep_requests_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, SweepRequest> {
+        ProofMapIndex::new(SWEEP_REQUESTS, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`sweep_signatures`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.sweep_signatures
+    pub fn sweep_signatures_mut(&mut self) -> ProofMapIndex<&mut Fork, TxInputId, InputSignatures> {
+        ProofMapIndex::new(SWEEP_SIGNATURES, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`sweep_transactions`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.sweep_transactions
+    pub fn sweep_transactions_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Transaction> {
+        ProofMapIndex::new(SWEEP_TRANSACTIONS, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`proposal_heights`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.proposal_heights
+    pub fn proposal_heights_mut(&mut self) -> MapIndex<&mut Fork, Hash, u64> {
+        MapIndex::new(PROPOSAL_HEIGHTS, &mut self.snapshot)
+    }
+
+    /// Remembers the height at which the actual proposal became available and forgets
+    /// the heights of the other proposals.
+    pub fn update_proposal_heights(&mut self, actual_proposal_id: Option<Hash>, height: Height) {
+        let stale_proposals = self
+            .proposal_heights()
+            .keys()
+            .filter(|txid| Some(*txid) != actual_proposal_id)
+            .collect::<Vec<_>>();
+
+        let mut proposal_heights = self.proposal_heights_mut();
+        for txid in stale_proposals {
+            proposal_heights.remove(&txid);
+        }
+        if let Some(txid) = actual_proposal_id {
+            if !proposal_heights.contains(&txid) {
+                proposal_heights.put(&txid, height.0);
+            }
+        }
+    }
+
+    /// Removes signatures for the inputs of all transactions except for the given
+    /// actual proposal, that is for the finalized and abandoned proposals.
+    pub fn prune_transaction_signatures(&mut self, actual_proposal_id: Option<Hash>) {
+        let stale_inputs = self
+            .transaction_signatures()
+            .keys()
+            .filter(|input_id| Some(input_id.txid) != actual_proposal_id)
+            .collect::<Vec<_>>();
+
+        let mut transaction_signatures = self.transaction_signatures_mut();
+        for input_id in stale_inputs {
+            transaction_signatures.remove(&input_id);
+        }
+    }
+
+    /// Removes the anchoring requests and votes for the already anchored heights.
+    pub fn prune_anchoring_requests(&mut self) {
+        let latest_anchored_height = match self.latest_anchored_height() {
+            Some(height) => height.0,
+            None => return,
+        };
+        let stale_requests = self
+            .anchoring_requests()
+            .iter()
+            .filter(|(_, height)| *height <= latest_anchored_height)
+            .map(|(block_hash, _)| block_hash)
+            .collect::<Vec<_>>();
+        let stale_votes = {
+            let blocks = Schema::new(&self.snapshot).blocks();
+            self.anchoring_request_votes()
+                .keys()
+                .filter(|block_hash| {
+                    blocks
+                        .get(block_hash)
+                        .map_or(true, |block| block.height().0 <= latest_anchored_height)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut anchoring_requests = self.anchoring_requests_mut();
+        for block_hash in stale_requests {
+            anchoring_requests.remove(&block_hash);
+        }
+        let mut anchoring_request_votes = self.anchoring_request_votes_mut();
+        for block_hash in stale_votes {
+            anchoring_request_votes.remove(&block_hash);
+        }
+    }
+
+    /// Removes the expired sweep requests along with the requests which no longer spend
+    /// the retired anchoring addresses, and the signatures for the abandoned sweep proposals.
+    pub fn prune_sweep_requests(&mut self, current_height: Height) {
+        let retired_script_pubkeys = self
+            .retired_configurations()
+            .iter()
+            .map(RetiredConfiguration::script_pubkey)
+            .collect::<Vec<_>>();
+        let stale_requests = self
+            .sweep_requests()
+            .iter()
+            .filter(|(_, request)| {
+                let is_expired = request.height.0 + SWEEP_REQUEST_LIFETIME <= current_height.0;
+                let is_retired = retired_script_pubkeys
+                    .iter()
+                    .any(|script_pubkey| request.transaction.find_out(script_pubkey).is_some());
+                is_expired || !is_retired
+            })
+            .map(|(txid, _)| txid)
+            .collect::<Vec<_>>();
+        let mut sweep_requests = self.sweep_requests_mut();
+        for txid in stale_requests {
+            trace!("Sweep request for the transaction {} is expired.", txid);
+            sweep_requests.remove(&txid);
+        }
+
+        let actual_proposal_id = self
+            .proposed_sweep_transaction()
+            .and_then(Result::ok)
+            .map(|(proposal, _)| proposal.id());
+        let stale_inputs = self
+            .sweep_signatures()
+            .keys()
+            .filter(|input_id| Some(input_id.txid) != actual_proposal_id)
+            .collect::<Vec<_>>();
+        let mut sweep_signatures = self.sweep_signatures_mut();
+        for input_id in stale_inputs {
+            sweep_signatures.remove(&input_id);
+        }
+    }
+
+    /// Removes the votes of the validators for the requests which are not approved yet.
+    /// Votes refer to the validators by their identifiers, so they should be removed
+    /// when the consensus configuration is changed.
+    pub fn clear_request_votes(&mut self) {
+        self.anchoring_request_votes_mut().clear();
+        self.sweep_request_votes_mut().clear();
+    }
+}
+
+/// Returns the Exonum service keys of the anchoring signers of the given anchoring
+/// configuration. In the legacy mode signers are the validators of the consensus
+/// configuration along with which the anchoring configuration is actual.
+fn signer_service_keys(
+    config: &GlobalConfig,
+    consensus_configuration: &StoredConfiguration,
+) -> Vec<crypto::PublicKey> {
+    if config.signers.is_empty() {
+        consensus_configuration
+            .validator_keys
+            .iter()
+            .take(config.public_keys.len())
+            .map(|keys| keys.service_key)
+            .collect()
+    } else {
+        config.signers.clone()
+    }
 }
@@ -14,29 +14,32 @@
 
 //! BTC anchoring transactions.
 
-use exonum::{
-    blockchain::{ExecutionResult, Transaction, TransactionContext},
-    helpers::ValidatorId,
-};
+use exonum::blockchain::{ExecutionResult, Schema as CoreSchema, Transaction, TransactionContext};
+use exonum::crypto::Hash;
+use exonum::helpers::{Height, ValidatorId};
 use exonum_derive::{ProtobufConvert, TransactionSet};
 
-use btc_transaction_utils::{p2wsh::InputSigner, InputSignature, TxInRef};
+use btc_transaction_utils::{multisig::RedeemScript, p2wsh::InputSigner, InputSignature, TxInRef};
 use log::{info, trace};
 use serde_derive::{Deserialize, Serialize};
 
 use crate::btc;
+use crate::config::{byzantine_quorum, SignerId};
 use crate::proto;
 
-use super::data_layout::TxInputId;
-use super::errors::SignatureError;
+use super::data_layout::{AnchoringSigners, InputSignatures, SweepRequest, TxInputId};
+use super::errors::{AnchoringRequestError, SignatureError};
 use super::BtcAnchoringSchema;
 
 /// Exonum message with the signature for the new anchoring transaction.
+///
+/// This message embeds the whole anchoring transaction and is kept only for the backward
+/// compatibility, use [`TxCompactSignature`](struct.TxCompactSignature.html) instead.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
 #[exonum(pb = "proto::TxSignature")]
 pub struct TxSignature {
-    /// Public key index in the anchoring public keys list.
-    pub validator: ValidatorId,
+    /// Identifier of the anchoring signer.
+    pub signer: SignerId,
     /// Signed Bitcoin anchoring transaction.
     pub transaction: btc::Transaction,
     /// Signed input.
@@ -45,11 +48,92 @@ pub struct TxSignature {
     pub input_signature: btc::InputSignature,
 }
 
+/// Exonum message with the signature for the new anchoring transaction which refers
+/// to the signed transaction by its identifier.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::TxCompactSignature")]
+pub struct TxCompactSignature {
+    /// Identifier of the anchoring signer.
+    pub signer: SignerId,
+    /// Identifier of the signed Bitcoin anchoring transaction.
+    pub txid: Hash,
+    /// Signed input.
+    pub input: u32,
+    /// Signature content.
+    pub input_signature: btc::InputSignature,
+}
+
+/// Exonum message with the signatures for all inputs of the new anchoring transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::TxSignatureBatch")]
+pub struct TxSignatureBatch {
+    /// Identifier of the anchoring signer.
+    pub signer: SignerId,
+    /// Identifier of the signed Bitcoin anchoring transaction.
+    pub txid: Hash,
+    /// Signatures content ordered by the input indices.
+    pub input_signatures: Vec<btc::InputSignature>,
+}
+
+/// Exonum message with the request to anchor the block at the given height out of
+/// the anchoring schedule.
+///
+/// The request is approved once it is sent by the byzantine majority of validators or
+/// by the anchoring administrator whose key is set in the anchoring configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::TxAnchoringRequest")]
+pub struct TxAnchoringRequest {
+    /// Height of the block to be anchored.
+    pub height: u64,
+}
+
+/// Exonum message with the request to sweep the funds sent to the retired anchoring
+/// addresses into the actual anchoring address.
+///
+/// The request is approved once it is sent by the byzantine majority of validators, then
+/// the outputs of the given transaction to the retired anchoring addresses are spent by
+/// the separate sweep transaction signed by the signers of the retired configurations
+/// with the [`TxSweepSignature`](struct.TxSweepSignature.html) messages. The request
+/// expires if the sweep transaction is not signed in time.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::TxSweepRequest")]
+pub struct TxSweepRequest {
+    /// Transaction with the outputs to the retired anchoring addresses.
+    pub transaction: btc::Transaction,
+}
+
+/// Exonum message with the signature for the proposed sweep transaction. The signer is
+/// identified by the author of the message in the retired configuration of the address
+/// spent by the signed input.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::TxSweepSignature")]
+pub struct TxSweepSignature {
+    /// Identifier of the signed sweep transaction.
+    pub txid: Hash,
+    /// Signed input.
+    pub input: u32,
+    /// Signature content.
+    pub input_signature: btc::InputSignature,
+}
+
 /// Exonum BTC anchoring transactions.
 #[derive(Serialize, Deserialize, Clone, Debug, TransactionSet)]
 pub enum Transactions {
     /// Exonum message with the signature for the new anchoring transaction.
     Signature(TxSignature),
+    /// Exonum message with the signatures for all inputs of the new anchoring transaction.
+    SignatureBatch(TxSignatureBatch),
+    /// Exonum message with the signature for the new anchoring transaction which refers
+    /// to the signed transaction by its identifier.
+    CompactSignature(TxCompactSignature),
+    /// Exonum message with the request to anchor the block at the given height out of
+    /// the anchoring schedule.
+    AnchoringRequest(TxAnchoringRequest),
+    /// Exonum message with the request to sweep the funds sent to the retired anchoring
+    /// addresses into the actual anchoring address.
+    SweepRequest(TxSweepRequest),
+    /// Exonum message with the signature for the sweep transaction.
+    SweepSignature(TxSweepSignature),
 }
 
 impl TxSignature {
@@ -62,112 +146,527 @@ impl TxSignature {
     }
 }
 
+impl TxCompactSignature {
+    /// Returns identifier of the signed transaction input.
+    pub fn input_id(&self) -> TxInputId {
+        TxInputId {
+            txid: self.txid,
+            input: self.input,
+        }
+    }
+}
+
 impl Transaction for TxSignature {
+    fn execute(&self, context: TransactionContext) -> ExecutionResult {
+        apply_signatures(
+            context,
+            self.signer,
+            self.transaction.id(),
+            &[(self.input as usize, &self.input_signature)],
+            false,
+        )
+    }
+}
+
+impl Transaction for TxCompactSignature {
+    fn execute(&self, context: TransactionContext) -> ExecutionResult {
+        apply_signatures(
+            context,
+            self.signer,
+            self.txid,
+            &[(self.input as usize, &self.input_signature)],
+            false,
+        )
+    }
+}
+
+impl Transaction for TxSignatureBatch {
+    fn execute(&self, context: TransactionContext) -> ExecutionResult {
+        let signatures = self.input_signatures.iter().enumerate().collect::<Vec<_>>();
+        apply_signatures(context, self.signer, self.txid, &signatures, true)
+    }
+}
+
+impl Transaction for TxAnchoringRequest {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let (author_validator_id, validators_count, block_hash) = {
+            let core_schema = CoreSchema::new(&*context.fork());
+            let validator_keys = core_schema.actual_configuration().validator_keys;
+            let author_validator_id = validator_keys
+                .iter()
+                .position(|keys| keys.service_key == author)
+                .map(|id| ValidatorId(id as u16));
+            (
+                author_validator_id,
+                validator_keys.len(),
+                core_schema.block_hashes_by_height().get(self.height),
+            )
+        };
+        let mut schema = BtcAnchoringSchema::new(context.fork());
+        let config = schema
+            .try_actual_configuration()
+            .map_err(|_| AnchoringRequestError::Misconfigured)?;
+
+        let height = Height(self.height);
+        let is_anchored = schema
+            .latest_anchored_height()
+            .map_or(false, |latest_anchored_height| {
+                height <= latest_anchored_height
+            });
+        let block_hash = match block_hash {
+            Some(block_hash) if !is_anchored => block_hash,
+            _ => return Err(AnchoringRequestError::IncorrectHeight(height).into()),
+        };
+
+        if config.admin_key != Some(author) {
+            let validator_id =
+                author_validator_id.ok_or(AnchoringRequestError::UnauthorizedRequest)?;
+            let mut votes = schema
+                .anchoring_request_votes()
+                .get(&block_hash)
+                .unwrap_or_default();
+            votes.insert(validator_id);
+            if votes.count() < byzantine_quorum(validators_count) {
+                schema.anchoring_request_votes_mut().put(&block_hash, votes);
+                return Ok(());
+            }
+            schema.anchoring_request_votes_mut().remove(&block_hash);
+        }
+
+        trace!("Anchoring of the block at height {} is requested.", height);
+        schema.anchoring_requests_mut().put(&block_hash, height.0);
+        Ok(())
+    }
+}
+
+impl Transaction for TxSweepRequest {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
-        // TODO Checks that transaction author is validator.
-        let tx = &self.transaction;
+        let author = context.author();
+        let (author_validator_id, validators_count, current_height) = {
+            let core_schema = CoreSchema::new(&*context.fork());
+            let validator_keys = core_schema.actual_configuration().validator_keys;
+            let author_validator_id = validator_keys
+                .iter()
+                .position(|keys| keys.service_key == author)
+                .map(|id| ValidatorId(id as u16));
+            (
+                author_validator_id,
+                validator_keys.len(),
+                Height(core_schema.block_hashes_by_height().len()),
+            )
+        };
         let mut schema = BtcAnchoringSchema::new(context.fork());
-        // Checks that the number of signatures is sufficient to spend.
-        if schema
-            .anchoring_transactions_chain()
-            .last()
-            .map(|tx| tx.id())
-            == Some(tx.id())
+        if schema.try_actual_configuration().is_err() {
+            return Err(AnchoringRequestError::Misconfigured.into());
+        }
+
+        let txid = self.transaction.id();
+        let is_retired = schema.retired_configurations().iter().any(|retired| {
+            self.transaction
+                .find_out(&retired.script_pubkey())
+                .is_some()
+        });
+        // Outputs of the anchoring transactions are spent by the following ones, so
+        // the anchoring transactions, which are recorded along with their signers,
+        // cannot be swept.
+        if !is_retired
+            || schema.spent_funding_transactions().contains(&txid)
+            || schema.anchoring_signers().contains(&txid)
+            || schema.sweep_requests().contains(&txid)
         {
+            return Err(AnchoringRequestError::UnsuitableSweepTransaction.into());
+        }
+
+        // Sweeping requires the approval of the validators, since the requested transaction
+        // cannot be checked against the Bitcoin blockchain during the execution.
+        let validator_id = author_validator_id.ok_or(AnchoringRequestError::UnauthorizedRequest)?;
+        let mut votes = schema.sweep_request_votes().get(&txid).unwrap_or_default();
+        votes.insert(validator_id);
+        if votes.count() < byzantine_quorum(validators_count) {
+            schema.sweep_request_votes_mut().put(&txid, votes);
             return Ok(());
         }
+        schema.sweep_request_votes_mut().remove(&txid);
 
-        let (expected_transaction, expected_inputs) = schema
-            .actual_proposed_anchoring_transaction()
-            .ok_or(SignatureError::InTransition)?
-            .map_err(SignatureError::TxBuilderError)?;
+        trace!("Sweeping of the transaction {} is requested.", txid);
+        schema.sweep_requests_mut().put(
+            &txid,
+            SweepRequest {
+                transaction: self.transaction.clone(),
+                height: current_height,
+            },
+        );
+        Ok(())
+    }
+}
 
-        if expected_transaction.id() != tx.id() {
+impl Transaction for TxSweepSignature {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        let author = context.author();
+        let mut schema = BtcAnchoringSchema::new(context.fork());
+        // The sweep transaction is already finalized.
+        if schema.sweep_transactions().contains(&self.txid) {
+            return Ok(());
+        }
+
+        let (proposal, proposal_inputs) = schema
+            .proposed_sweep_transaction()
+            .ok_or(SignatureError::NoSweepProposal)?
+            .map_err(SignatureError::TxBuilderError)?;
+        if proposal.id() != self.txid {
             return Err(SignatureError::Unexpected {
-                expected_id: expected_transaction.id(),
-                received_id: tx.id(),
+                expected_id: proposal.id(),
+                received_id: self.txid,
             }
             .into());
         }
 
-        let redeem_script = schema.actual_state().actual_configuration().redeem_script();
-        let redeem_script_content = redeem_script.content();
-        let public_key = if let Some(pk) = redeem_script_content
-            .public_keys
-            .get(self.validator.0 as usize)
-        {
-            pk
-        } else {
-            return Err(SignatureError::MissingPublicKey {
-                validator_id: self.validator,
-            }
-            .into());
+        // Each input is signed according to the retired configuration of the spent address.
+        let input_configurations = proposal_inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input_tx)| {
+                schema
+                    .sweep_input_configuration(&proposal, index, input_tx)
+                    .map(|retired| (retired.configuration.redeem_script(), retired))
+                    .ok_or(SignatureError::NoSuchInput { idx: index })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let input_idx = self.input as usize;
+        let (input_tx, (redeem_script, retired)) = match (
+            proposal_inputs.get(input_idx),
+            input_configurations.get(input_idx),
+        ) {
+            (Some(input_tx), Some(input_redeem_script)) => (input_tx, input_redeem_script),
+            _ => return Err(SignatureError::NoSuchInput { idx: input_idx }.into()),
         };
+        let signer_id = retired
+            .signer_id(&author)
+            .ok_or(SignatureError::UnauthorizedSweepSigner)?;
+        let public_key = redeem_script
+            .content()
+            .public_keys
+            .get(signer_id.0 as usize)
+            .ok_or(SignatureError::MissingPublicKey { signer_id })?;
+        InputSigner::new(redeem_script.clone())
+            .verify_input(
+                TxInRef::new(proposal.as_ref(), input_idx),
+                input_tx.as_ref(),
+                public_key,
+                self.input_signature.as_ref(),
+            )
+            .map_err(|_| SignatureError::VerificationFailed)?;
+
+        let input_id = TxInputId::new(self.txid, self.input);
+        let mut input_signatures = schema
+            .sweep_signatures()
+            .get(&input_id)
+            .unwrap_or_else(|| InputSignatures::new(redeem_script.content().public_keys.len()));
+        if input_signatures.len() == redeem_script.content().quorum {
+            return Ok(());
+        }
+        input_signatures.insert(signer_id, self.input_signature.clone().into());
+        schema
+            .sweep_signatures_mut()
+            .put(&input_id, input_signatures);
 
-        let input_signer = InputSigner::new(redeem_script.clone());
+        // Tries to finalize transaction.
+        let mut tx = proposal;
+        for (index, (redeem_script, _)) in input_configurations.iter().enumerate() {
+            let input_id = TxInputId::new(self.txid, index as u32);
+            let input_signatures = match schema.sweep_signatures().get(&input_id) {
+                Some(input_signatures)
+                    if input_signatures.len() == redeem_script.content().quorum =>
+                {
+                    input_signatures
+                }
+                _ => return Ok(()),
+            };
 
-        // Checks signature content.
-        let input_signature_ref = self.input_signature.as_ref();
-        let input_idx = self.input as usize;
+            InputSigner::new(redeem_script.clone()).spend_input(
+                &mut tx.0.input[index],
+                input_signatures
+                    .into_iter()
+                    .map(|bytes| InputSignature::from_bytes(bytes).unwrap()),
+            );
+        }
+
+        info!("====== SWEEP ======");
+        info!("txid: {}", tx.id().to_hex());
+        info!("balance: {}", tx.0.output[0].value);
+        trace!("Sweep txhex: {}", tx.to_string());
+
+        // All inputs spend the outputs of the same requested transaction.
+        let swept_tx = proposal_inputs[0].clone();
+        schema.sweep_requests_mut().remove(&swept_tx.id());
+        schema
+            .spent_funding_transactions_mut()
+            .put(&swept_tx.id(), swept_tx);
+        for index in 0..proposal_inputs.len() {
+            let input_id = TxInputId::new(self.txid, index as u32);
+            schema.sweep_signatures_mut().remove(&input_id);
+        }
+        schema.sweep_transactions_mut().put(&tx.id(), tx);
+        Ok(())
+    }
+}
+
+/// Verifies the given signatures of the proposed anchoring transaction inputs and adds
+/// them to the schema. Signatures are verified atomically, that is, none of them are
+/// added if at least one is incorrect. If the signatures are sufficient, the anchoring
+/// transaction is finalized.
+fn apply_signatures(
+    mut context: TransactionContext,
+    signer: SignerId,
+    txid: Hash,
+    signatures: &[(usize, &btc::InputSignature)],
+    all_inputs: bool,
+) -> ExecutionResult {
+    let author = context.author();
+    let (author_validator_id, current_height) = {
+        let core_schema = CoreSchema::new(&*context.fork());
+        let author_validator_id = core_schema
+            .actual_configuration()
+            .validator_keys
+            .iter()
+            .position(|keys| keys.service_key == author)
+            .map(|id| SignerId(id as u16));
+        (
+            author_validator_id,
+            Height(core_schema.block_hashes_by_height().len()),
+        )
+    };
+    let mut schema = BtcAnchoringSchema::new(context.fork());
+    // Checks that the number of signatures is sufficient to spend.
+    if schema
+        .anchoring_transactions_chain()
+        .last()
+        .map(|tx| tx.id())
+        == Some(txid)
+    {
+        return Ok(());
+    }
+    match schema.try_actual_configuration() {
+        Err(_) => return Err(SignatureError::Misconfigured.into()),
+        Ok(ref config) if config.is_paused(current_height) => {
+            return Err(SignatureError::Paused.into())
+        }
+        Ok(_) => {}
+    }
+
+    let (expected_transaction, expected_inputs) = schema
+        .actual_proposed_anchoring_transaction()
+        .ok_or(SignatureError::InTransition)?
+        .map_err(SignatureError::TxBuilderError)?;
+
+    if expected_transaction.id() != txid {
+        return Err(SignatureError::Unexpected {
+            expected_id: expected_transaction.id(),
+            received_id: txid,
+        }
+        .into());
+    }
+
+    if all_inputs && signatures.len() != expected_inputs.len() {
+        return Err(SignatureError::InputsCountMismatch {
+            expected: expected_inputs.len(),
+            actual: signatures.len(),
+        }
+        .into());
+    }
+
+    let state = schema
+        .try_actual_state()
+        .map_err(|_| SignatureError::Misconfigured)?;
+    let config = state.actual_configuration();
+    if config.signers.is_empty() {
+        // In the legacy mode signers are validators.
+        if author_validator_id != Some(signer) {
+            return Err(SignatureError::ValidatorMismatch { signer_id: signer }.into());
+        }
+    } else {
+        let service_key = config
+            .signer_service_key(signer)
+            .ok_or(SignatureError::MissingPublicKey { signer_id: signer })?;
+        if service_key != &author {
+            return Err(SignatureError::UnauthorizedSigner { signer_id: signer }.into());
+        }
+    }
+
+    let redeem_script = config.redeem_script();
+    let redeem_script_content = redeem_script.content();
+    if redeem_script_content.public_keys.len() <= signer.0 as usize {
+        return Err(SignatureError::MissingPublicKey { signer_id: signer }.into());
+    }
+
+    // Inputs may be locked by different redeem scripts, so the signer may have
+    // a different identifier in the redeem script of each input.
+    let input_signers = expected_inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input_tx)| {
+            schema.input_signer(
+                &state,
+                &expected_transaction,
+                index,
+                input_tx,
+                signer,
+                &author,
+            )
+        })
+        .collect::<Vec<_>>();
+    let input_signer_id = |input_signer: &(RedeemScript, Option<SignerId>)| {
+        input_signer
+            .1
+            .ok_or(SignatureError::MissingPublicKey { signer_id: signer })
+    };
+
+    // Checks signatures content.
+    for &(input_idx, input_signature) in signatures {
         let input_tx = match expected_inputs.get(input_idx) {
             Some(input_tx) => input_tx,
             _ => return Err(SignatureError::NoSuchInput { idx: input_idx }.into()),
         };
+        let input_redeem_script = &input_signers[input_idx].0;
+        let input_signer = input_signer_id(&input_signers[input_idx])?;
+        let public_key = input_redeem_script
+            .content()
+            .public_keys
+            .get(input_signer.0 as usize)
+            .ok_or(SignatureError::MissingPublicKey { signer_id: signer })?;
 
-        let verification_result = input_signer.verify_input(
-            TxInRef::new(tx.as_ref(), self.input as usize),
+        let verification_result = InputSigner::new(input_redeem_script.clone()).verify_input(
+            TxInRef::new(expected_transaction.as_ref(), input_idx),
             input_tx.as_ref(),
-            &public_key,
-            input_signature_ref,
+            public_key,
+            input_signature.as_ref(),
         );
 
         if verification_result.is_err() {
             return Err(SignatureError::VerificationFailed.into());
         }
+    }
+
+    let is_first_signature =
+        input_signers
+            .iter()
+            .enumerate()
+            .all(|(index, (input_redeem_script, input_signer))| {
+                let input_id = TxInputId::new(txid, index as u32);
+                input_signer.map_or(true, |input_signer| {
+                    !schema
+                        .input_signatures(&input_id, input_redeem_script)
+                        .contains(input_signer)
+                })
+            });
 
-        let input_id = self.input_id();
-        let mut input_signatures = schema.input_signatures(&input_id, &redeem_script);
-        if input_signatures.len() != redeem_script_content.quorum {
-            // Adds signature to schema.
-            input_signatures.insert(self.validator, self.input_signature.clone().into());
+    // Adds signatures to schema.
+    let mut signatures_added = false;
+    for &(input_idx, input_signature) in signatures {
+        let input_id = TxInputId::new(txid, input_idx as u32);
+        let input_redeem_script = &input_signers[input_idx].0;
+        let input_signer = input_signer_id(&input_signers[input_idx])?;
+        let mut input_signatures = schema.input_signatures(&input_id, input_redeem_script);
+        if input_signatures.len() != input_redeem_script.content().quorum {
+            input_signatures.insert(input_signer, input_signature.clone().into());
             schema
                 .transaction_signatures_mut()
                 .put(&input_id, input_signatures);
-            // Tries to finalize transaction.
-            let mut tx: btc::Transaction = tx.clone();
-            for index in 0..expected_inputs.len() {
-                let input_id = TxInputId::new(self.transaction.id(), index as u32);
-                let input_signatures = schema.input_signatures(&input_id, &redeem_script);
-
-                if input_signatures.len() != redeem_script_content.quorum {
-                    return Ok(());
-                }
+            signatures_added = true;
+        }
+    }
 
-                input_signer.spend_input(
-                    &mut tx.0.input[index],
-                    input_signatures
-                        .into_iter()
-                        .map(|bytes| InputSignature::from_bytes(bytes).unwrap()),
-                );
-            }
+    if !signatures_added {
+        return Ok(());
+    }
 
-            let payload = tx.anchoring_metadata().unwrap().1;
+    // Updates the signature latency of the signer.
+    if is_first_signature {
+        let proposal_height = schema
+            .proposal_heights()
+            .get(&txid)
+            .unwrap_or_else(|| current_height.0.saturating_sub(1));
+        let public_key = &config.public_keys[signer.0 as usize];
+        let mut stats = schema.signers_stats().get(public_key).unwrap_or_default();
+        stats.latency_sum += current_height.0.saturating_sub(proposal_height);
+        stats.latency_samples += 1;
+        schema.signers_stats_mut().put(public_key, stats);
+    }
 
-            info!("====== ANCHORING ======");
-            info!("txid: {}", tx.id().to_hex());
-            info!("height: {}", payload.block_height);
-            info!("hash: {}", payload.block_hash.to_hex());
-            info!("balance: {}", tx.0.output[0].value);
-            trace!("Anchoring txhex: {}", tx.to_string());
+    // Tries to finalize transaction.
+    let mut tx = expected_transaction;
+    let mut anchoring_signers =
+        AnchoringSigners::new(redeem_script_content.public_keys.len(), current_height);
+    for (index, (input_redeem_script, _)) in input_signers.iter().enumerate() {
+        let input_id = TxInputId::new(txid, index as u32);
+        let input_signatures = schema.input_signatures(&input_id, input_redeem_script);
 
-            // Adds finalized transaction to the tail of anchoring transactions.
-            schema.anchoring_transactions_chain_mut().push(tx);
-            if let Some(unspent_funding_tx) = schema.unspent_funding_transaction() {
-                schema
-                    .spent_funding_transactions_mut()
-                    .put(&unspent_funding_tx.id(), unspent_funding_tx);
+        if input_signatures.len() != input_redeem_script.content().quorum {
+            return Ok(());
+        }
+
+        // Statistics are collected for the signers of the actual configuration only.
+        if input_redeem_script.as_ref() == redeem_script.as_ref() {
+            for signer_id in input_signatures.signers() {
+                anchoring_signers.insert(signer_id);
             }
         }
-        Ok(())
+
+        InputSigner::new(input_redeem_script.clone()).spend_input(
+            &mut tx.0.input[index],
+            input_signatures
+                .into_iter()
+                .map(|bytes| InputSignature::from_bytes(bytes).unwrap()),
+        );
+    }
+
+    let payload = tx.anchoring_metadata().unwrap().1;
+
+    info!("====== ANCHORING ======");
+    info!("txid: {}", tx.id().to_hex());
+    info!("height: {}", payload.block_height);
+    info!("hash: {}", payload.block_hash.to_hex());
+    info!("balance: {}", tx.0.output[0].value);
+    trace!("Anchoring txhex: {}", tx.to_string());
+
+    // Adds finalized transaction to the tail of anchoring transactions.
+    schema.anchoring_transactions_chain_mut().push(tx);
+    // Updates participation statistics of the signers.
+    for (index, public_key) in config.public_keys.iter().enumerate() {
+        let mut stats = schema.signers_stats().get(public_key).unwrap_or_default();
+        if anchoring_signers.contains(SignerId(index as u16)) {
+            stats.signed += 1;
+        } else {
+            stats.missed += 1;
+        }
+        schema.signers_stats_mut().put(public_key, stats);
+    }
+    schema.anchoring_signers_mut().put(&txid, anchoring_signers);
+    // Signatures are kept in the transaction witnesses, so they can be removed.
+    for index in 0..expected_inputs.len() {
+        let input_id = TxInputId::new(txid, index as u32);
+        schema.transaction_signatures_mut().remove(&input_id);
+    }
+    if let Some(unspent_funding_tx) = schema.unspent_funding_transaction() {
+        schema
+            .spent_funding_transactions_mut()
+            .put(&unspent_funding_tx.id(), unspent_funding_tx);
+    }
+    if let Some(following_funding_tx) = schema.unspent_following_funding_transaction(&state) {
+        if expected_inputs.contains(&following_funding_tx) {
+            schema
+                .spent_funding_transactions_mut()
+                .put(&following_funding_tx.id(), following_funding_tx);
+        }
+    }
+    for sweep_tx in schema.unspent_sweep_transactions() {
+        if expected_inputs.contains(&sweep_tx) {
+            schema
+                .spent_funding_transactions_mut()
+                .put(&sweep_tx.id(), sweep_tx);
+        }
     }
+    Ok(())
 }
@@ -14,16 +14,20 @@
 
 //! Collection of wrappers for the rust-bitcoin crate.
 
-pub use self::payload::Payload;
-pub use self::transaction::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
+pub use self::payload::{CurrentPayloadFormat, Payload, PayloadError, PayloadFormat, PayloadV1};
+pub use self::transaction::{
+    BtcAnchoringTransactionBuilder, BtcSweepTransactionBuilder, BuilderError, Transaction,
+};
 
 use bitcoin::network::constants::Network;
 use bitcoin::util::address;
 use btc_transaction_utils;
 use derive_more::{From, Into};
+use exonum::storage::StorageKey;
 use hex::{self, FromHex, ToHex};
 
 use rand::Rng;
+use secp256k1::Secp256k1;
 use std::ops::Deref;
 
 #[macro_use]
@@ -50,6 +54,13 @@ pub struct Address(pub address::Address);
 #[derive(Debug, Clone, PartialEq, Into, From)]
 pub struct InputSignature(pub btc_transaction_utils::InputSignature);
 
+impl PrivateKey {
+    /// Returns the public key corresponding to this private key.
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(self.0.public_key(&Secp256k1::new()))
+    }
+}
+
 impl ToString for PrivateKey {
     fn to_string(&self) -> String {
         self.0.to_string()
@@ -94,6 +105,26 @@ impl ToHex for PublicKey {
     }
 }
 
+impl StorageKey for PublicKey {
+    fn size(&self) -> usize {
+        if self.0.compressed {
+            secp256k1::constants::PUBLIC_KEY_SIZE
+        } else {
+            secp256k1::constants::UNCOMPRESSED_PUBLIC_KEY_SIZE
+        }
+    }
+
+    fn write(&self, buffer: &mut [u8]) {
+        let mut bytes = Vec::with_capacity(self.size());
+        self.0.write_into(&mut bytes);
+        buffer.copy_from_slice(&bytes);
+    }
+
+    fn read(buffer: &[u8]) -> Self {
+        PublicKey(bitcoin::PublicKey::from_slice(buffer).unwrap())
+    }
+}
+
 impl ::std::str::FromStr for Address {
     type Err = <address::Address as ::std::str::FromStr>::Err;
 
@@ -18,6 +18,7 @@ use exonum::helpers::Height;
 use bitcoin::blockdata::opcodes::all::OP_RETURN;
 use bitcoin::blockdata::script::{Builder, Instruction, Script};
 use byteorder::{ByteOrder, LittleEndian};
+use failure_derive::Fail;
 use serde_derive::{Deserialize, Serialize};
 
 const PAYLOAD_PREFIX: &[u8] = b"EXONUM";
@@ -25,21 +26,23 @@ const PAYLOAD_HEADER_LEN: usize = 8;
 const PAYLOAD_V1: u8 = 1;
 const PAYLOAD_V1_KIND_REGULAR: u8 = 0;
 const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
+const PAYLOAD_V1_KIND_STATE: u8 = 2;
+
+/// Payload format which is used for the new anchoring transactions.
+pub type CurrentPayloadFormat = PayloadV1;
 
 /// Anchoring transaction payload.
 ///
-/// Data layout in `OP_RETURN` script for `Payload` v.1:
+/// Payload is stored in the `OP_RETURN` script and starts with the common header:
 ///
 /// | Position in bytes     | Description                                       |
 /// |-----------------------|---------------------------------------------------|
-/// | 0..6                  | ASCII-encoded prefix `EXONUM` 	                |
-/// | 6                     | Version byte, currently is 1             	        |
-/// | 7                     | Payload kind: (0 is regular, 1 is recover)        |
-/// | 8..16                 | Block height                                      |
-/// | 16..48                | Block hash                                        |
-/// | 48..80 (Optionally)   | Txid of previous tx chain (only for recover kind) |
+/// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
+/// | 6                     | Version byte                                      |
+/// | 7                     | Payload kind byte                                 |
 ///
-/// In this way the length of `regular` payload is 48, and for `recover` is 80.
+/// The rest of the data layout depends on the version and the kind, see
+/// [`PayloadV1`](struct.PayloadV1.html) for the details.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Payload {
     /// Anchored block height.
@@ -48,108 +51,145 @@ pub struct Payload {
     pub block_hash: Hash,
     /// `Txid` of previous transactions chain if it has been lost.
     pub prev_tx_chain: Option<Hash>,
+    /// State hash of the anchored block if it is committed by the payload.
+    #[serde(default)]
+    pub state_hash: Option<Hash>,
 }
 
-#[derive(Debug)]
-enum PayloadV1 {
-    Regular(Height, Hash),
-    Recover(Height, Hash, Hash),
+/// Possible errors during the payload decoding.
+#[derive(Debug, Copy, Clone, PartialEq, Fail)]
+pub enum PayloadError {
+    /// The script is not an `OP_RETURN` script with the pushed data.
+    #[fail(display = "The script is not an `OP_RETURN` script with the pushed data.")]
+    NotDataCarrier,
+    /// The pushed data doesn't start with the Exonum payload header.
+    #[fail(display = "The pushed data doesn't start with the Exonum payload header.")]
+    MissingHeader,
+    /// The payload version is unknown.
+    #[fail(display = "Unknown payload version {}.", _0)]
+    UnknownVersion(u8),
+    /// The payload kind is unknown for the given version.
+    #[fail(display = "Unknown payload kind {} for version {}.", kind, version)]
+    UnknownKind {
+        /// Payload version.
+        version: u8,
+        /// Payload kind.
+        kind: u8,
+    },
+    /// The payload data has an unexpected length.
+    #[fail(
+        display = "Unexpected payload data length {}, expected {}.",
+        actual, expected
+    )]
+    UnexpectedLength {
+        /// Expected data length.
+        expected: usize,
+        /// Actual data length.
+        actual: usize,
+    },
 }
 
-#[derive(Debug, Default)]
-pub struct PayloadV1Builder {
-    block_hash: Option<Hash>,
-    block_height: Option<Height>,
-    prev_tx_chain: Option<Hash>,
-}
+/// Binary format of the specific payload version.
+///
+/// Every version is responsible for its own set of payload kinds, thus the new kinds
+/// can be added to the new versions while the older ones are still decoded.
+pub trait PayloadFormat {
+    /// Version byte of the format.
+    const VERSION: u8;
 
-pub type PayloadBuilder = PayloadV1Builder;
-
-#[cfg_attr(feature = "cargo-clippy", allow(clippy::len_without_is_empty))]
-impl PayloadV1 {
-    fn read(bytes: &[u8]) -> Option<Self> {
-        let kind = bytes[0];
-        let data = &bytes[1..];
-        match kind {
-            PAYLOAD_V1_KIND_REGULAR => {
-                if data.len() != 40 {
-                    return None;
-                }
-
-                let block_height = LittleEndian::read_u64(&data[0..8]);
-                let block_hash = Hash::from_slice(&data[8..40]).unwrap();
-                Some(PayloadV1::Regular(Height(block_height), block_hash))
-            }
-            PAYLOAD_V1_KIND_RECOVER => {
-                if data.len() != 72 {
-                    return None;
-                }
-
-                let block_height = LittleEndian::read_u64(&data[0..8]);
-                let block_hash = Hash::from_slice(&data[8..40]).unwrap();
-                let txid = Hash::from_slice(&data[40..72]).unwrap();
-                Some(PayloadV1::Recover(Height(block_height), block_hash, txid))
-            }
-            _ => None,
-        }
-    }
+    /// Decodes the payload of the given kind from the data following the header.
+    fn decode(kind: u8, data: &[u8]) -> Result<Payload, PayloadError>;
 
-    fn write(&self, buf: &mut [u8]) {
-        let kind = self.kind();
-        buf[0] = kind as u8;
+    /// Encodes the payload and returns its kind byte and the data following the header.
+    fn encode(payload: &Payload) -> (u8, Vec<u8>);
+}
 
-        let buf = &mut buf[1..];
-        debug_assert_eq!(buf.len(), self.len());
-        // Serialize data
-        match *self {
-            PayloadV1::Regular(height, hash) => {
-                LittleEndian::write_u64(&mut buf[0..8], height.0);
-                buf[8..40].copy_from_slice(hash.as_ref());
-            }
-            PayloadV1::Recover(height, hash, txid) => {
-                LittleEndian::write_u64(&mut buf[0..8], height.0);
-                buf[8..40].copy_from_slice(hash.as_ref());
-                buf[40..72].copy_from_slice(txid.as_ref());
+/// Payload format version 1.
+///
+/// Data layout after the payload header:
+///
+/// | Position in bytes     | Description                                       |
+/// |-----------------------|---------------------------------------------------|
+/// | 0..8                  | Block height                                      |
+/// | 8..40                 | Block hash                                        |
+/// | 40..72 (Optionally)   | Txid of previous tx chain (only for recover kind) |
+/// | 40..72 (Optionally)   | State hash of the block (only for state kind)     |
+///
+/// Kind byte is 0 for the regular payload, 1 for the recover one and 2 for the payload
+/// which also commits to the block state hash. In this way the total length of `regular`
+/// payload is 48, and for `recover` and `state` is 80.
+///
+/// Since the data pushed by the standard `OP_RETURN` script is limited by 80 bytes,
+/// the `recover` payload takes precedence and doesn't contain the state hash.
+#[derive(Debug)]
+pub struct PayloadV1;
+
+impl PayloadFormat for PayloadV1 {
+    const VERSION: u8 = PAYLOAD_V1;
+
+    fn decode(kind: u8, data: &[u8]) -> Result<Payload, PayloadError> {
+        let expected = match kind {
+            PAYLOAD_V1_KIND_REGULAR => 40,
+            PAYLOAD_V1_KIND_RECOVER | PAYLOAD_V1_KIND_STATE => 72,
+            kind => {
+                return Err(PayloadError::UnknownKind {
+                    version: Self::VERSION,
+                    kind,
+                });
             }
         };
-    }
-
-    fn len(&self) -> usize {
-        match *self {
-            PayloadV1::Regular(..) => 40,
-            PayloadV1::Recover(..) => 72,
+        if data.len() != expected {
+            return Err(PayloadError::UnexpectedLength {
+                expected,
+                actual: data.len(),
+            });
         }
+
+        let block_height = Height(LittleEndian::read_u64(&data[0..8]));
+        let block_hash = Hash::from_slice(&data[8..40]).unwrap();
+        let tail = || Some(Hash::from_slice(&data[40..72]).unwrap());
+        let (prev_tx_chain, state_hash) = match kind {
+            PAYLOAD_V1_KIND_RECOVER => (tail(), None),
+            PAYLOAD_V1_KIND_STATE => (None, tail()),
+            _ => (None, None),
+        };
+        Ok(Payload {
+            block_height,
+            block_hash,
+            prev_tx_chain,
+            state_hash,
+        })
     }
 
-    fn kind(&self) -> u8 {
-        match *self {
-            PayloadV1::Regular(..) => PAYLOAD_V1_KIND_REGULAR,
-            PayloadV1::Recover(..) => PAYLOAD_V1_KIND_RECOVER,
+    fn encode(payload: &Payload) -> (u8, Vec<u8>) {
+        let mut data = vec![0; 40];
+        LittleEndian::write_u64(&mut data[0..8], payload.block_height.0);
+        data[8..40].copy_from_slice(payload.block_hash.as_ref());
+
+        if let Some(txid) = payload.prev_tx_chain {
+            data.extend_from_slice(txid.as_ref());
+            (PAYLOAD_V1_KIND_RECOVER, data)
+        } else if let Some(state_hash) = payload.state_hash {
+            data.extend_from_slice(state_hash.as_ref());
+            (PAYLOAD_V1_KIND_STATE, data)
+        } else {
+            (PAYLOAD_V1_KIND_REGULAR, data)
         }
     }
+}
 
-    fn into_script(self) -> Script {
-        let len = self.len() + PAYLOAD_HEADER_LEN;
-        let mut buf = vec![0; len];
-        // Serialize header
-        buf[0..6].copy_from_slice(PAYLOAD_PREFIX);
-        buf[6] = PAYLOAD_V1;
-        self.write(&mut buf[7..]);
-        // Build script
-        Builder::new()
-            .push_opcode(OP_RETURN)
-            .push_slice(buf.as_ref())
-            .into_script()
-    }
+/// Builder for the anchoring payload script.
+#[derive(Debug, Default)]
+pub struct PayloadBuilder {
+    block_hash: Option<Hash>,
+    block_height: Option<Height>,
+    prev_tx_chain: Option<Hash>,
+    state_hash: Option<Hash>,
 }
 
-impl PayloadV1Builder {
+impl PayloadBuilder {
     pub fn new() -> Self {
-        Self {
-            block_hash: None,
-            block_height: None,
-            prev_tx_chain: None,
-        }
+        Self::default()
     }
 
     pub fn block_height(mut self, height: Height) -> Self {
@@ -167,67 +207,67 @@ impl PayloadV1Builder {
         self
     }
 
-    pub fn into_script(self) -> Script {
-        let block_height = self.block_height.expect("Block height is not set");
-        let block_hash = self.block_hash.expect("Block hash is not set");
+    pub fn state_hash(mut self, state_hash: Option<Hash>) -> Self {
+        self.state_hash = state_hash;
+        self
+    }
 
-        let payload = match self.prev_tx_chain {
-            Some(txid) => PayloadV1::Recover(block_height, block_hash, txid),
-            None => PayloadV1::Regular(block_height, block_hash),
-        };
-        payload.into_script()
+    pub fn into_payload(self) -> Payload {
+        Payload {
+            block_height: self.block_height.expect("Block height is not set"),
+            block_hash: self.block_hash.expect("Block hash is not set"),
+            prev_tx_chain: self.prev_tx_chain,
+            state_hash: self.state_hash,
+        }
+    }
+
+    pub fn into_script(self) -> Script {
+        self.into_payload().to_script::<CurrentPayloadFormat>()
     }
 }
 
 impl Payload {
-    /// Tries to extract payload from given `Script`
-    pub fn from_script(script: &Script) -> Option<Self> {
+    /// Tries to extract payload from given `Script`.
+    pub fn from_script(script: &Script) -> Result<Self, PayloadError> {
         let mut instructions = script.iter(true);
-        instructions
-            .next()
-            .and_then(|instr| {
-                if instr == Instruction::Op(OP_RETURN) {
-                    instructions.next()
-                } else {
-                    None
-                }
-            })
-            .and_then(|instr| {
-                if let Instruction::PushBytes(bytes) = instr {
-                    if bytes.len() < PAYLOAD_HEADER_LEN {
-                        return None;
-                    }
-                    if &bytes[0..6] != PAYLOAD_PREFIX {
-                        return None;
-                    }
-                    // Parse metadata
-                    let version = bytes[6];
-                    match version {
-                        PAYLOAD_V1 => PayloadV1::read(&bytes[7..]).map(Self::from),
-                        _ => None,
-                    }
-                } else {
-                    None
-                }
-            })
-    }
-}
+        if instructions.next() != Some(Instruction::Op(OP_RETURN)) {
+            return Err(PayloadError::NotDataCarrier);
+        }
 
-impl From<PayloadV1> for Payload {
-    fn from(v1: PayloadV1) -> Self {
-        match v1 {
-            PayloadV1::Regular(height, hash) => Self {
-                block_height: height,
-                block_hash: hash,
-                prev_tx_chain: None,
-            },
-            PayloadV1::Recover(height, hash, txid) => Self {
-                block_height: height,
-                block_hash: hash,
-                prev_tx_chain: Some(txid),
-            },
+        let bytes = match instructions.next() {
+            Some(Instruction::PushBytes(bytes)) => bytes,
+            _ => return Err(PayloadError::NotDataCarrier),
+        };
+        if bytes.len() < PAYLOAD_HEADER_LEN || &bytes[0..6] != PAYLOAD_PREFIX {
+            return Err(PayloadError::MissingHeader);
+        }
+        // Parse metadata.
+        let version = bytes[6];
+        let kind = bytes[7];
+        let data = &bytes[PAYLOAD_HEADER_LEN..];
+        match version {
+            PayloadV1::VERSION => PayloadV1::decode(kind, data),
+            version => Err(PayloadError::UnknownVersion(version)),
         }
     }
+
+    /// Converts payload into the `OP_RETURN` script using the given format.
+    pub fn to_script<F: PayloadFormat>(&self) -> Script {
+        let (kind, data) = F::encode(self);
+
+        let mut buf = Vec::with_capacity(PAYLOAD_HEADER_LEN + data.len());
+        // Serialize header
+        buf.extend_from_slice(PAYLOAD_PREFIX);
+        buf.push(F::VERSION);
+        buf.push(kind);
+        // Serialize data
+        buf.extend_from_slice(&data);
+        // Build script
+        Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(buf.as_ref())
+            .into_script()
+    }
 }
 
 #[cfg(test)]
@@ -238,7 +278,7 @@ mod tests {
     use bitcoin::blockdata::script::Script;
     use hex;
 
-    use super::{Payload, PayloadBuilder};
+    use super::{Payload, PayloadBuilder, PayloadError, PayloadFormat, PayloadV1};
 
     trait HexValue {
         fn from_hex(hex: impl AsRef<[u8]>) -> Self;
@@ -284,6 +324,7 @@ mod tests {
         assert_eq!(payload.block_hash, block_hash);
         assert_eq!(payload.block_height, Height(1234));
         assert_eq!(payload.prev_tx_chain, None);
+        assert_eq!(payload.state_hash, None);
     }
 
     #[test]
@@ -321,6 +362,40 @@ mod tests {
         assert_eq!(payload.prev_tx_chain, Some(prev_txid));
     }
 
+    #[test]
+    fn test_payload_state_serialize_deserialize() {
+        let block_hash = hash(&[]);
+        let state_hash = hash(&[1, 2, 3]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .state_hash(Some(state_hash))
+            .into_script();
+
+        assert_eq!(payload_script.len(), 83);
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.prev_tx_chain, None);
+        assert_eq!(payload.state_hash, Some(state_hash));
+    }
+
+    #[test]
+    fn test_payload_recover_takes_precedence_over_state() {
+        let block_hash = hash(&[]);
+        let prev_txid = hash(&[4, 5, 6]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .prev_tx_chain(Some(prev_txid))
+            .state_hash(Some(hash(&[1, 2, 3])))
+            .into_script();
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.prev_tx_chain, Some(prev_txid));
+        assert_eq!(payload.state_hash, None);
+    }
+
     #[test]
     fn test_payload_incorrect_deserialize() {
         // Payload from old anchoring transaction
@@ -328,13 +403,63 @@ mod tests {
             "6a2a0128f0b31a00000000008fb4879f1b7f332be1aee197f99f\
              7333c915570c6ad5c6eed641f33fe0199129",
         );
-        assert_eq!(Payload::from_script(&payload_script), None);
+        assert_eq!(
+            Payload::from_script(&payload_script),
+            Err(PayloadError::MissingHeader)
+        );
     }
 
     #[test]
     fn test_payload_non_op_return() {
         // Payload from old anchoring transaction
         let script_pubkey = Script::from_hex("a91472b7506704dc074fa46359251052e781d96f939a87");
-        assert_eq!(Payload::from_script(&script_pubkey), None);
+        assert_eq!(
+            Payload::from_script(&script_pubkey),
+            Err(PayloadError::NotDataCarrier)
+        );
+    }
+
+    #[test]
+    fn test_payload_unknown_version() {
+        // Regular payload with the version byte set to 0xff.
+        let payload_script = Script::from_hex(
+            "6a3045584f4e554dff00d204000000000000e3b0c44298fc1c14\
+             9afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(
+            Payload::from_script(&payload_script),
+            Err(PayloadError::UnknownVersion(0xff))
+        );
+    }
+
+    #[test]
+    fn test_payload_unknown_kind() {
+        let payload_script = Script::from_hex(
+            "6a3045584f4e554d0107d204000000000000e3b0c44298fc1c14\
+             9afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(
+            Payload::from_script(&payload_script),
+            Err(PayloadError::UnknownKind {
+                version: PayloadV1::VERSION,
+                kind: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_payload_unexpected_length() {
+        // Recover kind with the regular payload data.
+        let payload_script = Script::from_hex(
+            "6a3045584f4e554d0101d204000000000000e3b0c44298fc1c14\
+             9afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        );
+        assert_eq!(
+            Payload::from_script(&payload_script),
+            Err(PayloadError::UnexpectedLength {
+                expected: 72,
+                actual: 40
+            })
+        );
     }
 }
@@ -59,7 +59,7 @@ impl Transaction {
     /// Returns the anchoring payload for the transaction if it is the anchoring transaction.
     pub fn anchoring_payload(&self) -> Option<Payload> {
         let out = self.0.output.get(1)?;
-        Payload::from_script(&out.script_pubkey)
+        Payload::from_script(&out.script_pubkey).ok()
     }
 
     /// Returns the complete meta information for the transaction
@@ -87,6 +87,7 @@ pub struct BtcAnchoringTransactionBuilder {
     additional_funds: Vec<(usize, Transaction)>,
     fee: Option<u64>,
     payload: Option<(Height, Hash)>,
+    state_hash: Option<Hash>,
 }
 
 /// Anchoring transaction builder errors.
@@ -126,6 +127,7 @@ impl BtcAnchoringTransactionBuilder {
             additional_funds: Vec::default(),
             fee: None,
             payload: None,
+            state_hash: None,
         }
     }
 
@@ -162,6 +164,21 @@ impl BtcAnchoringTransactionBuilder {
         Ok(())
     }
 
+    /// Add a funding transaction sent to the address specified by the `transit_to` method.
+    /// Its corresponding unspent output will be used as an additional input for the
+    /// transition transaction if the funds of the actual anchoring address are insufficient
+    /// to pay the fee.
+    pub fn transition_funds(&mut self, tx: Transaction) -> Result<(), BuilderError> {
+        let out = self
+            .transit_to
+            .as_ref()
+            .and_then(|script_pubkey| tx.find_out(script_pubkey))
+            .ok_or_else(|| BuilderError::UnsuitableFundingTx)?
+            .0;
+        self.additional_funds.push((out, tx));
+        Ok(())
+    }
+
     /// Sets the fee per byte value.
     pub fn fee(&mut self, fee: u64) {
         self.fee = Some(fee);
@@ -172,6 +189,11 @@ impl BtcAnchoringTransactionBuilder {
         self.payload = Some((block_height, block_hash));
     }
 
+    /// Adds the state hash of the anchored block to the anchoring transaction payload.
+    pub fn state_hash(&mut self, state_hash: Hash) {
+        self.state_hash = Some(state_hash);
+    }
+
     /// Finalizes the anchoring transaction and returns
     /// it and also the list of input transactions.
     pub fn create(mut self) -> Result<(Transaction, Vec<Transaction>), BuilderError> {
@@ -208,6 +230,7 @@ impl BtcAnchoringTransactionBuilder {
             .block_hash(block_hash)
             .block_height(block_height)
             .prev_tx_chain(self.recovery_tx)
+            .state_hash(self.state_hash)
             .into_script();
         let output = match self.transit_to {
             Some(script) => script,
@@ -246,6 +269,99 @@ impl BtcAnchoringTransactionBuilder {
     }
 }
 
+/// Builder for the transactions which sweep the funds sent to the retired anchoring
+/// addresses into the actual anchoring address. Unlike the anchoring transactions,
+/// sweep transactions have no payload and are not included into the anchoring chain.
+#[derive(Debug)]
+pub struct BtcSweepTransactionBuilder {
+    script_pubkey: Script,
+    retired_funds: Vec<(usize, Transaction)>,
+    fee: Option<u64>,
+}
+
+impl BtcSweepTransactionBuilder {
+    /// Creates a new sweep transaction builder for the given redeem script
+    /// of the actual anchoring address.
+    pub fn new(redeem_script: &RedeemScript) -> BtcSweepTransactionBuilder {
+        BtcSweepTransactionBuilder {
+            script_pubkey: redeem_script.as_ref().to_v0_p2wsh(),
+            retired_funds: Vec::default(),
+            fee: None,
+        }
+    }
+
+    /// Add a transaction with the outputs to the given retired anchoring address.
+    /// All its outputs to this address will be used as inputs of the sweep transaction.
+    pub fn retired_funds(
+        &mut self,
+        tx: Transaction,
+        script_pubkey: &Script,
+    ) -> Result<(), BuilderError> {
+        let outs =
+            tx.0.output
+                .iter()
+                .enumerate()
+                .filter(|(_, out)| &out.script_pubkey == script_pubkey)
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+        if outs.is_empty() {
+            return Err(BuilderError::UnsuitableFundingTx);
+        }
+        for out in outs {
+            self.retired_funds.push((out, tx.clone()));
+        }
+        Ok(())
+    }
+
+    /// Sets the fee per byte value.
+    pub fn fee(&mut self, fee: u64) {
+        self.fee = Some(fee);
+    }
+
+    /// Finalizes the sweep transaction and returns it and also the list of input
+    /// transactions.
+    pub fn create(self) -> Result<(Transaction, Vec<Transaction>), BuilderError> {
+        if self.retired_funds.is_empty() {
+            return Err(BuilderError::NoInputs);
+        }
+
+        let mut input = Vec::new();
+        let mut input_transactions = Vec::new();
+        let mut balance = 0;
+        for (out_index, tx) in self.retired_funds {
+            input.push(TxIn {
+                previous_output: OutPoint {
+                    txid: tx.0.txid(),
+                    vout: out_index as u32,
+                },
+                script_sig: Script::default(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::default(),
+            });
+            balance += tx.0.output[out_index].value;
+            input_transactions.push(tx);
+        }
+
+        let mut transaction = Transaction::from(transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input,
+            output: vec![TxOut {
+                value: balance,
+                script_pubkey: self.script_pubkey,
+            }],
+        });
+
+        let size_in_bytes = ::bitcoin::consensus::serialize(&transaction.0).len() as u64;
+        let total_fee = self.fee.expect("Fee per byte isn't set.") * size_in_bytes;
+        if total_fee > balance {
+            return Err(BuilderError::InsufficientFunds { total_fee, balance });
+        }
+        transaction.0.output[0].value -= total_fee;
+        Ok((transaction, input_transactions))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use exonum::crypto::CryptoHash;
@@ -268,7 +384,9 @@ mod tests {
 
     use crate::btc::PublicKey;
 
-    use super::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
+    use super::{
+        BtcAnchoringTransactionBuilder, BtcSweepTransactionBuilder, BuilderError, Transaction,
+    };
 
     #[test]
     fn test_transaction_conversions() {
@@ -529,6 +647,69 @@ mod tests {
         assert_eq!(out_1.value, 0);
     }
 
+    #[test]
+    fn test_sweep_transaction_builder() {
+        let retired_tx: Transaction = Transaction::from_hex(
+            "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+             dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+             cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+             eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+             e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+             021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+             062c1400",
+        )
+        .unwrap();
+
+        let keys = vec![
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0.clone())
+        .collect::<Vec<_>>();
+
+        let retired_redeem_script = RedeemScriptBuilder::with_public_keys(keys.clone())
+            .to_script()
+            .unwrap();
+        let actual_redeem_script = RedeemScriptBuilder::with_public_keys(keys[1..].to_vec())
+            .to_script()
+            .unwrap();
+
+        let mut builder = BtcSweepTransactionBuilder::new(&actual_redeem_script);
+        assert_matches!(
+            builder.retired_funds(
+                retired_tx.clone(),
+                &actual_redeem_script.as_ref().to_v0_p2wsh()
+            ),
+            Err(BuilderError::UnsuitableFundingTx)
+        );
+        builder
+            .retired_funds(
+                retired_tx.clone(),
+                &retired_redeem_script.as_ref().to_v0_p2wsh(),
+            )
+            .unwrap();
+        builder.fee(1);
+        let (tx, inputs) = builder.create().unwrap();
+
+        assert_eq!(inputs, vec![retired_tx.clone()]);
+        assert_eq!(tx.0.input.len(), 1);
+        assert_eq!(tx.0.input[0].previous_output.vout, 1);
+        assert_eq!(tx.0.output.len(), 1);
+        assert_eq!(
+            tx.0.output[0].script_pubkey,
+            actual_redeem_script.as_ref().to_v0_p2wsh()
+        );
+        assert!(tx.0.output[0].value < retired_tx.0.output[1].value);
+        assert!(tx.anchoring_payload().is_none());
+
+        let mut builder = BtcSweepTransactionBuilder::new(&actual_redeem_script);
+        builder.fee(1);
+        assert_matches!(builder.create(), Err(BuilderError::NoInputs));
+    }
+
     #[test]
     fn test_anchoring_transaction_builder_incorrect_prev_tx() {
         let funding_tx: Transaction = Transaction::from_hex(
@@ -611,4 +792,55 @@ mod tests {
             BuilderError::UnsuitableFundingTx
         );
     }
+
+    #[test]
+    fn test_anchoring_transaction_builder_transition_funds() {
+        let funding_tx: Transaction = Transaction::from_hex(
+            "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+             dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+             cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+             eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+             e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+             021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+             062c1400",
+        )
+        .unwrap();
+
+        let keys = vec![
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0.clone())
+        .collect::<Vec<_>>();
+
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys.clone())
+            .to_script()
+            .unwrap();
+        let actual_redeem_script = RedeemScriptBuilder::with_public_keys(keys[0..2].to_vec())
+            .to_script()
+            .unwrap();
+
+        // Transition address is not set.
+        let mut builder = BtcAnchoringTransactionBuilder::new(&actual_redeem_script);
+        assert_matches!(
+            builder.transition_funds(funding_tx.clone()).unwrap_err(),
+            BuilderError::UnsuitableFundingTx
+        );
+
+        // Funding transaction is sent to the following address.
+        builder.transit_to(redeem_script.as_ref().to_v0_p2wsh());
+        builder.transition_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), funding_tx.hash());
+        let (tx, inputs) = builder.create().unwrap();
+
+        assert_eq!(inputs, vec![funding_tx]);
+        assert_eq!(
+            tx.0.output[0].script_pubkey,
+            redeem_script.as_ref().to_v0_p2wsh()
+        );
+    }
 }
@@ -14,16 +14,21 @@
 
 //! BTC anchoring configuration data types.
 
-use exonum::helpers::Height;
+use exonum::crypto;
+use exonum::helpers::{Height, ValidatorId};
 
 use bitcoin::network::constants::Network;
 use btc_transaction_utils::multisig::{RedeemScript, RedeemScriptBuilder, RedeemScriptError};
 use btc_transaction_utils::p2wsh;
+use failure_derive::Fail;
 use serde_derive::{Deserialize, Serialize};
 
 use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
 
 use crate::btc::{Address, PrivateKey, PublicKey, Transaction};
+use crate::keystore::EncryptedPrivateKey;
 use crate::rpc::BitcoinRpcConfig;
 
 /// Returns sufficient number of keys for the given validators number.
@@ -31,6 +36,90 @@ pub fn byzantine_quorum(total: usize) -> usize {
     ::exonum::node::state::State::byzantine_majority_count(total)
 }
 
+/// Identifier of the anchoring signer, that is the index of its Bitcoin public key
+/// in the anchoring public keys list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SignerId(pub u16);
+
+impl fmt::Display for SignerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<ValidatorId> for SignerId {
+    fn from(id: ValidatorId) -> Self {
+        SignerId(id.0)
+    }
+}
+
+/// Policy of choosing the anchored heights when the anchoring falls behind the blockchain,
+/// for example, after the pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatchUpPolicy {
+    /// Anchors every missed height one after another.
+    EveryHeight,
+    /// Anchors only the latest height which must be anchored and skips the missed ones.
+    LatestHeight,
+}
+
+impl Default for CatchUpPolicy {
+    fn default() -> Self {
+        CatchUpPolicy::EveryHeight
+    }
+}
+
+/// Possible errors of the anchoring configuration validation.
+#[derive(Debug, Fail)]
+pub enum ConfigError {
+    /// The public keys list is empty.
+    #[fail(display = "Anchoring public keys list is empty.")]
+    EmptyPublicKeys,
+    /// The public key occurs in the list more than once.
+    #[fail(display = "Anchoring public key {} is duplicated.", _0)]
+    DuplicatePublicKey(PublicKey),
+    /// Unable to create the redeem script with the given public keys and quorum.
+    #[fail(display = "Unable to create the anchoring redeem script: {}", _0)]
+    RedeemScript(RedeemScriptError),
+    /// The anchoring interval is zero.
+    #[fail(display = "Anchoring interval should be positive.")]
+    ZeroAnchoringInterval,
+    /// The number of signers doesn't match the number of public keys.
+    #[fail(
+        display = "Number of signers {} doesn't match the number of public keys {}.",
+        signers, public_keys
+    )]
+    SignersCountMismatch {
+        /// Number of the anchoring signers.
+        signers: usize,
+        /// Number of the anchoring public keys.
+        public_keys: usize,
+    },
+    /// The funding transaction doesn't pay to the anchoring address.
+    #[fail(
+        display = "Funding transaction {} doesn't pay to the anchoring address.",
+        _0
+    )]
+    UnsuitableFundingTransaction(crypto::Hash),
+}
+
+/// List of the errors found by the anchoring configuration validation.
+#[derive(Debug)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid BTC anchoring configuration:")?;
+        for error in &self.0 {
+            write!(f, " {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl failure::Fail for ConfigErrors {}
+
 /// Consensus parameters in the BTC anchoring.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GlobalConfig {
@@ -44,6 +133,36 @@ pub struct GlobalConfig {
     pub transaction_fee: u64,
     /// Funding transaction.
     pub funding_transaction: Option<Transaction>,
+    /// Commit to the state hash of the anchored block in addition to its hash.
+    #[serde(default)]
+    pub anchor_state_hash: bool,
+    /// Number of signatures required to spend the anchoring output. If it is not set,
+    /// the byzantine majority of the public keys is used.
+    #[serde(default)]
+    pub quorum: Option<usize>,
+    /// Exonum service keys of the anchoring signers. The signer with the given identifier
+    /// uses the Bitcoin public key with the same index. If the list is empty, the signers
+    /// are the validators and the signer identifiers coincide with the validator ones.
+    #[serde(default)]
+    pub signers: Vec<crypto::PublicKey>,
+    /// Whether the anchoring is enabled. Disabled anchoring can be resumed only by the
+    /// configuration change.
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// Height until which the anchoring is paused.
+    #[serde(default)]
+    pub paused_until_height: Option<Height>,
+    /// Policy of choosing the anchored heights when the anchoring resumes.
+    #[serde(default)]
+    pub catch_up_policy: CatchUpPolicy,
+    /// Exonum service key which is allowed to request the out-of-schedule anchoring
+    /// without the approval of the validators.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_key: Option<crypto::PublicKey>,
+}
+
+fn enabled_by_default() -> bool {
+    true
 }
 
 impl Default for GlobalConfig {
@@ -54,6 +173,13 @@ impl Default for GlobalConfig {
             anchoring_interval: 5_000,
             transaction_fee: 10,
             funding_transaction: None,
+            anchor_state_hash: false,
+            quorum: None,
+            signers: vec![],
+            enabled: true,
+            paused_until_height: None,
+            catch_up_policy: CatchUpPolicy::default(),
+            admin_key: None,
         }
     }
 }
@@ -82,13 +208,98 @@ impl GlobalConfig {
         p2wsh::address(&self.redeem_script(), self.network).into()
     }
 
-    /// Returns the corresponding redeem script.
-    pub fn redeem_script(&self) -> RedeemScript {
-        let quorum = byzantine_quorum(self.public_keys.len());
+    /// Returns the number of signatures required to spend the anchoring output.
+    pub fn quorum(&self) -> usize {
+        self.quorum
+            .unwrap_or_else(|| byzantine_quorum(self.public_keys.len()))
+    }
+
+    /// Returns the corresponding redeem script or an error if the quorum
+    /// doesn't match the public keys count.
+    pub fn try_redeem_script(&self) -> Result<RedeemScript, RedeemScriptError> {
+        let quorum = self.quorum();
+        if quorum == 0 || quorum > self.public_keys.len() {
+            Err(RedeemScriptError::IncorrectQuorum)?;
+        }
+
         RedeemScriptBuilder::with_public_keys(self.public_keys.iter().map(|x| x.0))
             .quorum(quorum)
             .to_script()
-            .unwrap()
+    }
+
+    /// Returns the corresponding redeem script.
+    pub fn redeem_script(&self) -> RedeemScript {
+        self.try_redeem_script().unwrap()
+    }
+
+    /// Checks that the configuration can be used for anchoring and returns the list of
+    /// all found problems otherwise.
+    pub fn validate(&self) -> Result<(), ConfigErrors> {
+        let mut errors = Vec::new();
+
+        if self.public_keys.is_empty() {
+            errors.push(ConfigError::EmptyPublicKeys);
+        }
+        for (index, public_key) in self.public_keys.iter().enumerate() {
+            if self.public_keys[..index].contains(public_key) {
+                errors.push(ConfigError::DuplicatePublicKey(*public_key));
+            }
+        }
+        if self.anchoring_interval == 0 {
+            errors.push(ConfigError::ZeroAnchoringInterval);
+        }
+        if !self.signers.is_empty() && self.signers.len() != self.public_keys.len() {
+            errors.push(ConfigError::SignersCountMismatch {
+                signers: self.signers.len(),
+                public_keys: self.public_keys.len(),
+            });
+        }
+
+        if !self.public_keys.is_empty() {
+            match self.try_redeem_script() {
+                Ok(redeem_script) => {
+                    if let Some(ref funding_tx) = self.funding_transaction {
+                        let address = p2wsh::address(&redeem_script, self.network);
+                        let is_suitable = funding_tx
+                            .find_out(&address.script_pubkey())
+                            .map_or(false, |(_, output)| output.value > 0);
+                        if !is_suitable {
+                            errors.push(ConfigError::UnsuitableFundingTransaction(funding_tx.id()));
+                        }
+                    }
+                }
+                Err(e) => errors.push(ConfigError::RedeemScript(e)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigErrors(errors))
+        }
+    }
+
+    /// Returns the identifier of the signer with the given Exonum service key, if the
+    /// signers list is set explicitly.
+    pub fn signer_id(&self, service_key: &crypto::PublicKey) -> Option<SignerId> {
+        self.signers
+            .iter()
+            .position(|key| key == service_key)
+            .map(|index| SignerId(index as u16))
+    }
+
+    /// Returns the Exonum service key of the signer with the given identifier, if the
+    /// signers list is set explicitly.
+    pub fn signer_service_key(&self, id: SignerId) -> Option<&crypto::PublicKey> {
+        self.signers.get(id.0 as usize)
+    }
+
+    /// Checks that the anchoring is disabled or paused at the given height.
+    pub fn is_paused(&self, height: Height) -> bool {
+        !self.enabled
+            || self
+                .paused_until_height
+                .map_or(false, |paused_until| height < paused_until)
     }
 
     /// Returns the latest height below the given height which must be anchored.
@@ -109,8 +320,97 @@ pub struct LocalConfig {
     /// to the Bitcoin network.
     pub rpc: Option<BitcoinRpcConfig>,
     /// Set of private keys for each anchoring address.
-    #[serde(with = "flatten_keypairs")]
+    #[serde(with = "flatten_keypairs", default)]
     pub private_keys: HashMap<Address, PrivateKey>,
+    /// Set of encrypted private keys for each anchoring address.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encrypted_private_keys: Vec<EncryptedKeypair>,
+    /// Path to the Unix socket of the external signer daemon. If this option is set,
+    /// anchoring transactions are signed by the daemon instead of the local private keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_signer: Option<PathBuf>,
+    /// Private keys which are not yet bound to any anchoring address.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending_private_keys: Vec<PrivateKey>,
+    /// Encrypted private keys which are not yet bound to any anchoring address.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encrypted_pending_private_keys: Vec<EncryptedPrivateKey>,
+}
+
+/// Anchoring address and the corresponding encrypted private key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EncryptedKeypair {
+    /// Bitcoin address.
+    pub address: Address,
+    /// Corresponding encrypted private key.
+    pub private_key: EncryptedPrivateKey,
+}
+
+impl LocalConfig {
+    /// Returns true if the configuration contains encrypted private keys.
+    pub fn is_locked(&self) -> bool {
+        !self.encrypted_private_keys.is_empty() || !self.encrypted_pending_private_keys.is_empty()
+    }
+
+    /// Encrypts all private keys with the given passphrase.
+    pub fn lock(&mut self, passphrase: &str) -> Result<(), failure::Error> {
+        for (address, private_key) in self.private_keys.drain() {
+            let private_key = EncryptedPrivateKey::encrypt(&private_key, passphrase)?;
+            self.encrypted_private_keys.push(EncryptedKeypair {
+                address,
+                private_key,
+            });
+        }
+        for private_key in self.pending_private_keys.drain(..) {
+            let private_key = EncryptedPrivateKey::encrypt(&private_key, passphrase)?;
+            self.encrypted_pending_private_keys.push(private_key);
+        }
+        Ok(())
+    }
+
+    /// Decrypts all encrypted private keys with the given passphrase.
+    pub fn unlock(&mut self, passphrase: &str) -> Result<(), failure::Error> {
+        let mut private_keys = HashMap::new();
+        for keypair in &self.encrypted_private_keys {
+            let private_key = keypair.private_key.decrypt(passphrase)?;
+            private_keys.insert(keypair.address.clone(), private_key);
+        }
+        let pending_private_keys = self
+            .encrypted_pending_private_keys
+            .iter()
+            .map(|private_key| private_key.decrypt(passphrase))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.private_keys.extend(private_keys);
+        self.pending_private_keys.extend(pending_private_keys);
+        self.encrypted_private_keys.clear();
+        self.encrypted_pending_private_keys.clear();
+        Ok(())
+    }
+
+    /// Binds the private key of this node to the anchoring address of the given configuration.
+    /// The key is searched among the pending keys and the keys of the other addresses.
+    /// Returns the corresponding public key or `None` if the node has no suitable key.
+    pub fn bind_private_key(&mut self, config: &GlobalConfig) -> Option<PublicKey> {
+        let address = config.anchoring_address();
+        let is_suitable =
+            |private_key: &PrivateKey| config.public_keys.contains(&private_key.public_key());
+
+        if let Some(index) = self.pending_private_keys.iter().position(is_suitable) {
+            let private_key = self.pending_private_keys.remove(index);
+            let public_key = private_key.public_key();
+            self.private_keys.insert(address, private_key);
+            return Some(public_key);
+        }
+
+        let private_key = self
+            .private_keys
+            .values()
+            .find(|private_key| is_suitable(private_key))
+            .cloned()?;
+        let public_key = private_key.public_key();
+        self.private_keys.insert(address, private_key);
+        Some(public_key)
+    }
 }
 
 /// BTC anchoring configuration.
@@ -174,13 +474,20 @@ mod flatten_keypairs {
 
 #[cfg(test)]
 mod tests {
+    use exonum::crypto;
     use exonum::helpers::Height;
 
     use bitcoin::network::constants::Network;
     use btc_transaction_utils::test_data::secp_gen_keypair;
 
-    use super::{GlobalConfig, LocalConfig};
+    use btc_transaction_utils::multisig::RedeemScriptError;
+    use maplit::hashmap;
+    use matches::assert_matches;
+
+    use super::{CatchUpPolicy, ConfigError, GlobalConfig, LocalConfig, SignerId};
+    use crate::btc::gen_keypair;
     use crate::rpc::BitcoinRpcConfig;
+    use crate::test_helpers::testkit::create_fake_funding_transaction;
 
     #[test]
     fn test_global_config() {
@@ -196,6 +503,134 @@ mod tests {
         assert_eq!(config2, config);
     }
 
+    #[test]
+    fn test_global_config_quorum() {
+        let public_keys = (0..4)
+            .map(|_| secp_gen_keypair(Network::Bitcoin).0.into())
+            .collect::<Vec<_>>();
+
+        let mut config = GlobalConfig::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        let byzantine_address = config.anchoring_address();
+
+        config.quorum = Some(4);
+        assert_eq!(config.redeem_script().content().quorum, 4);
+        assert_ne!(config.anchoring_address(), byzantine_address);
+
+        config.quorum = Some(1);
+        assert_eq!(config.redeem_script().content().quorum, 1);
+
+        config.quorum = Some(0);
+        assert_matches!(
+            config.try_redeem_script().unwrap_err(),
+            RedeemScriptError::IncorrectQuorum
+        );
+
+        config.quorum = Some(5);
+        assert_matches!(
+            config.try_redeem_script().unwrap_err(),
+            RedeemScriptError::IncorrectQuorum
+        );
+    }
+
+    #[test]
+    fn test_global_config_signers() {
+        let public_keys = (0..3)
+            .map(|_| secp_gen_keypair(Network::Bitcoin).0.into())
+            .collect::<Vec<_>>();
+        let service_keys = (0..3).map(|_| crypto::gen_keypair().0).collect::<Vec<_>>();
+
+        let mut config = GlobalConfig::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        assert_eq!(config.signer_id(&service_keys[0]), None);
+
+        config.signers = service_keys.clone();
+        assert_eq!(config.signer_id(&service_keys[2]), Some(SignerId(2)));
+        assert_eq!(
+            config.signer_service_key(SignerId(1)),
+            Some(&service_keys[1])
+        );
+        assert_eq!(config.signer_service_key(SignerId(3)), None);
+        assert_eq!(config.signer_id(&crypto::gen_keypair().0), None);
+    }
+
+    #[test]
+    fn test_global_config_validate() {
+        let public_keys = (0..4)
+            .map(|_| gen_keypair(Network::Testnet).0)
+            .collect::<Vec<_>>();
+        let mut config =
+            GlobalConfig::with_public_keys(Network::Testnet, public_keys.clone()).unwrap();
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+        config.funding_transaction = Some(funding_tx.clone());
+        config.validate().unwrap();
+
+        config.anchoring_interval = 0;
+        config.signers = (0..3).map(|_| crypto::gen_keypair().0).collect();
+        let errors = config.validate().unwrap_err().0;
+        assert_eq!(errors.len(), 2);
+        assert_matches!(errors[0], ConfigError::ZeroAnchoringInterval);
+        assert_matches!(
+            errors[1],
+            ConfigError::SignersCountMismatch {
+                signers: 3,
+                public_keys: 4
+            }
+        );
+
+        let mut config = GlobalConfig {
+            public_keys: public_keys[1..].to_vec(),
+            funding_transaction: Some(funding_tx.clone()),
+            ..GlobalConfig::default()
+        };
+        let errors = config.validate().unwrap_err().0;
+        assert_matches!(
+            errors[..],
+            [ConfigError::UnsuitableFundingTransaction(ref txid)] if *txid == funding_tx.id()
+        );
+
+        config.public_keys = vec![public_keys[0], public_keys[1], public_keys[0]];
+        config.funding_transaction = None;
+        let errors = config.validate().unwrap_err().0;
+        assert_matches!(errors[0], ConfigError::DuplicatePublicKey(ref key) if *key == public_keys[0]);
+
+        config.public_keys = public_keys;
+        config.quorum = Some(5);
+        let errors = config.validate().unwrap_err().0;
+        assert_matches!(
+            errors[..],
+            [ConfigError::RedeemScript(
+                RedeemScriptError::IncorrectQuorum
+            )]
+        );
+
+        let errors = GlobalConfig::default().validate().unwrap_err().0;
+        assert_matches!(errors[..], [ConfigError::EmptyPublicKeys]);
+    }
+
+    #[test]
+    fn test_global_config_pause() {
+        let public_keys = vec![gen_keypair(Network::Testnet).0];
+        let mut config = GlobalConfig::with_public_keys(Network::Testnet, public_keys).unwrap();
+        assert!(!config.is_paused(Height(0)));
+
+        config.paused_until_height = Some(Height(10));
+        assert!(config.is_paused(Height(9)));
+        assert!(!config.is_paused(Height(10)));
+
+        config.enabled = false;
+        assert!(config.is_paused(Height(10)));
+
+        // Configurations without the pause options are enabled.
+        let mut json = serde_json::to_value(&config).unwrap();
+        let object = json.as_object_mut().unwrap();
+        object.remove("enabled");
+        object.remove("paused_until_height");
+        object.remove("catch_up_policy");
+        let config: GlobalConfig = serde_json::from_value(json).unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.paused_until_height, None);
+        assert_eq!(config.catch_up_policy, CatchUpPolicy::EveryHeight);
+    }
+
     #[test]
     fn test_local_config() {
         let cfg_str = r#"
@@ -218,6 +653,79 @@ mod tests {
         assert!(local_config.private_keys.len() == 1);
     }
 
+    #[test]
+    fn test_local_config_lock() {
+        let (public_key, private_key) = gen_keypair(Network::Testnet);
+        let config = GlobalConfig::with_public_keys(Network::Testnet, vec![public_key]).unwrap();
+        let address = config.anchoring_address();
+
+        let mut local_config = LocalConfig {
+            rpc: None,
+            private_keys: hashmap! { address.clone() => private_key.clone() },
+            encrypted_private_keys: vec![],
+            remote_signer: None,
+            pending_private_keys: vec![],
+            encrypted_pending_private_keys: vec![],
+        };
+        local_config.lock("passphrase").unwrap();
+        assert!(local_config.is_locked());
+        assert!(local_config.private_keys.is_empty());
+
+        let toml = ::toml::to_string(&local_config).unwrap();
+        let mut local_config: LocalConfig = ::toml::from_str(&toml).unwrap();
+        assert!(local_config.unlock("wrong passphrase").is_err());
+        local_config.unlock("passphrase").unwrap();
+        assert!(!local_config.is_locked());
+        assert_eq!(
+            local_config.private_keys[&address].to_string(),
+            private_key.to_string()
+        );
+    }
+
+    #[test]
+    fn test_local_config_bind_private_key() {
+        let (public_key, private_key) = gen_keypair(Network::Testnet);
+        let (new_public_key, new_private_key) = gen_keypair(Network::Testnet);
+        let (other_public_key, _) = gen_keypair(Network::Testnet);
+        let config = GlobalConfig::with_public_keys(Network::Testnet, vec![public_key]).unwrap();
+
+        let mut local_config = LocalConfig {
+            rpc: None,
+            private_keys: hashmap! { config.anchoring_address() => private_key.clone() },
+            encrypted_private_keys: vec![],
+            remote_signer: None,
+            pending_private_keys: vec![new_private_key.clone()],
+            encrypted_pending_private_keys: vec![],
+        };
+
+        // The node has no suitable key.
+        let config =
+            GlobalConfig::with_public_keys(Network::Testnet, vec![other_public_key]).unwrap();
+        assert_eq!(local_config.bind_private_key(&config), None);
+        // The pending key is bound.
+        let config = GlobalConfig::with_public_keys(
+            Network::Testnet,
+            vec![new_public_key, other_public_key],
+        )
+        .unwrap();
+        assert_eq!(local_config.bind_private_key(&config), Some(new_public_key));
+        assert!(local_config.pending_private_keys.is_empty());
+        assert_eq!(
+            local_config.private_keys[&config.anchoring_address()],
+            new_private_key
+        );
+        // The key of the other address is bound.
+        let config =
+            GlobalConfig::with_public_keys(Network::Testnet, vec![other_public_key, public_key])
+                .unwrap();
+        assert_eq!(local_config.bind_private_key(&config), Some(public_key));
+        assert_eq!(
+            local_config.private_keys[&config.anchoring_address()],
+            private_key
+        );
+        assert_eq!(local_config.private_keys.len(), 3);
+    }
+
     #[test]
     fn test_global_config_anchoring_height() {
         let public_keys = (0..4)
@@ -24,8 +24,11 @@ use toml;
 use std::collections::BTreeMap;
 use std::str::FromStr;
 
+use bitcoin::consensus::encode;
 use exonum::crypto;
 
+use crate::btc;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Hash(pub crypto::Hash);
 
@@ -36,6 +39,18 @@ impl FromStr for Hash {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Transaction(pub btc::Transaction);
+
+impl FromStr for Transaction {
+    type Err = encode::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = Vec::<u8>::from_hex(s)
+            .map_err(|_| encode::Error::ParseFailed("Invalid transaction hex"))?;
+        encode::deserialize(&bytes).map(|tx| Transaction(btc::Transaction(tx)))
+    }
+}
+
 pub trait TypedArgument {
     type ParsedType: FromStr;
     type OutputType: Serialize + DeserializeOwned + Clone + Send + Sync;
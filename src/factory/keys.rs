@@ -0,0 +1,297 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Subcommands for the anchoring keys rotation.
+
+use exonum::helpers::config::ConfigFile;
+use exonum::node::NodeConfig;
+
+use failure::{bail, format_err};
+use serde_json::{json, Value};
+use structopt::StructOpt;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::btc::{gen_keypair, PublicKey};
+use crate::config::{Config, GlobalConfig, LocalConfig};
+use crate::keystore;
+use crate::BTC_ANCHORING_SERVICE_NAME;
+
+/// Subcommands for the anchoring keys rotation.
+///
+/// To change the anchoring keys each signer generates a new key by the `generate-key`
+/// subcommand. Then one of them collects the public keys and creates the configuration
+/// change proposal by the `propose-keys` subcommand, while the others bind their keys
+/// to the new anchoring address by the same subcommand with the same public keys.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "btc-anchoring")]
+pub enum KeysCommand {
+    /// Generates a new anchoring key pair, saves the private key to the local configuration
+    /// of the node and prints the public key.
+    #[structopt(name = "generate-key")]
+    GenerateKey {
+        /// Path to the node configuration file.
+        #[structopt(long = "node-config", parse(from_os_str))]
+        node_config: PathBuf,
+    },
+    /// Creates the anchoring configuration with the given public keys, prints the resulting
+    /// anchoring address and the configuration change proposal.
+    #[structopt(name = "propose-keys")]
+    ProposeKeys {
+        /// Hex-encoded Bitcoin public keys of the anchoring signers.
+        #[structopt(long = "public-keys", raw(required = "true"))]
+        public_keys: Vec<PublicKey>,
+        /// Number of signatures required to spend the anchoring output.
+        #[structopt(long = "quorum")]
+        quorum: Option<usize>,
+        /// Path to the file with the actual configuration returned by the
+        /// `v1/configs/actual` endpoint of the configuration service.
+        #[structopt(long = "actual-config", parse(from_os_str))]
+        actual_config: Option<PathBuf>,
+        /// Height since which the proposed configuration becomes actual.
+        #[structopt(long = "actual-from")]
+        actual_from: Option<u64>,
+        /// Path to the node configuration file. If it is set, the private key of the node
+        /// is bound to the new anchoring address.
+        #[structopt(long = "node-config", parse(from_os_str))]
+        node_config: Option<PathBuf>,
+    },
+}
+
+impl KeysCommand {
+    /// Executes the subcommand.
+    pub fn run(self) -> Result<(), failure::Error> {
+        match self {
+            KeysCommand::GenerateKey { node_config } => generate_key(&node_config),
+            KeysCommand::ProposeKeys {
+                public_keys,
+                quorum,
+                actual_config,
+                actual_from,
+                node_config,
+            } => propose_keys(
+                public_keys,
+                quorum,
+                actual_config.as_ref().map(PathBuf::as_path),
+                actual_from,
+                node_config.as_ref().map(PathBuf::as_path),
+            ),
+        }
+    }
+}
+
+fn generate_key(node_config_path: &Path) -> Result<(), failure::Error> {
+    let (mut node_config, mut config) = load_node_config(node_config_path)?;
+
+    let (public_key, private_key) = gen_keypair(config.global.network);
+    modify_local_config(&mut config.local, |local| {
+        local.pending_private_keys.push(private_key);
+        Ok(())
+    })?;
+    save_node_config(node_config_path, &mut node_config, config)?;
+
+    println!("{}", public_key);
+    Ok(())
+}
+
+fn propose_keys(
+    public_keys: Vec<PublicKey>,
+    quorum: Option<usize>,
+    actual_config_path: Option<&Path>,
+    actual_from: Option<u64>,
+    node_config_path: Option<&Path>,
+) -> Result<(), failure::Error> {
+    let actual_config = actual_config_path
+        .map(|path| -> Result<Value, failure::Error> {
+            Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+        })
+        .transpose()?;
+    let node = node_config_path
+        .map(|path| load_node_config(path).map(|(node_config, config)| (path, node_config, config)))
+        .transpose()?;
+
+    let base_config = if let Some(ref actual_config) = actual_config {
+        let (stored_config, _) = split_actual_config(actual_config);
+        serde_json::from_value(stored_config["services"][BTC_ANCHORING_SERVICE_NAME].clone())?
+    } else if let Some((_, _, ref config)) = node {
+        config.global.clone()
+    } else {
+        bail!("Either `--actual-config` or `--node-config` argument should be specified.");
+    };
+
+    let global = proposed_config(base_config, public_keys, quorum)?;
+    println!("Anchoring address: {}", global.anchoring_address());
+
+    if let Some((path, mut node_config, mut config)) = node {
+        let mut bound_key = None;
+        modify_local_config(&mut config.local, |local| {
+            bound_key = local.bind_private_key(&global);
+            Ok(())
+        })?;
+        if let Some(public_key) = bound_key {
+            save_node_config(path, &mut node_config, config)?;
+            println!(
+                "Private key for the public key {} is bound to the address.",
+                public_key
+            );
+        } else {
+            println!("Warning: node has no private key for the address.");
+        }
+    }
+
+    if let Some(actual_config) = actual_config {
+        let proposal = config_change_proposal(&actual_config, &global, actual_from)?;
+        println!("{}", serde_json::to_string_pretty(&proposal)?);
+    }
+    Ok(())
+}
+
+/// Returns the anchoring configuration with the given public keys and quorum
+/// based on the given one.
+fn proposed_config(
+    base_config: GlobalConfig,
+    public_keys: Vec<PublicKey>,
+    quorum: Option<usize>,
+) -> Result<GlobalConfig, failure::Error> {
+    let mut config = GlobalConfig {
+        public_keys,
+        quorum,
+        ..base_config.clone()
+    };
+    // The funding transaction of the previous configuration pays to the previous address.
+    if config.try_redeem_script().is_ok()
+        && config.anchoring_address() != base_config.anchoring_address()
+    {
+        config.funding_transaction = None;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+/// Returns the consensus configuration and its hash from the response of
+/// the `v1/configs/actual` endpoint. Also accepts the bare consensus configuration.
+fn split_actual_config(actual_config: &Value) -> (&Value, Option<&Value>) {
+    match actual_config.get("config") {
+        Some(config) => (config, actual_config.get("hash")),
+        None => (actual_config, None),
+    }
+}
+
+/// Creates the configuration change proposal with the given anchoring configuration.
+fn config_change_proposal(
+    actual_config: &Value,
+    global: &GlobalConfig,
+    actual_from: Option<u64>,
+) -> Result<Value, failure::Error> {
+    let (stored_config, hash) = split_actual_config(actual_config);
+
+    let mut proposal = stored_config.clone();
+    *proposal
+        .get_mut("services")
+        .and_then(|services| services.get_mut(BTC_ANCHORING_SERVICE_NAME))
+        .ok_or_else(|| format_err!("BTC anchoring configuration not found"))? =
+        serde_json::to_value(global)?;
+    if let Some(hash) = hash {
+        proposal["previous_cfg_hash"] = hash.clone();
+    }
+    if let Some(actual_from) = actual_from {
+        proposal["actual_from"] = json!(actual_from);
+    }
+    Ok(proposal)
+}
+
+fn load_node_config(path: &Path) -> Result<(NodeConfig<PathBuf>, Config), failure::Error> {
+    let node_config: NodeConfig<PathBuf> = ConfigFile::load(path)?;
+    let config = node_config
+        .services_configs
+        .get(BTC_ANCHORING_SERVICE_NAME)
+        .ok_or_else(|| format_err!("BTC anchoring config not found"))?
+        .clone()
+        .try_into()?;
+    Ok((node_config, config))
+}
+
+fn save_node_config(
+    path: &Path,
+    node_config: &mut NodeConfig<PathBuf>,
+    config: Config,
+) -> Result<(), failure::Error> {
+    node_config.services_configs.insert(
+        BTC_ANCHORING_SERVICE_NAME.to_owned(),
+        toml::Value::try_from(config)?,
+    );
+    ConfigFile::save(node_config, path)
+}
+
+/// Applies the given modification to the local configuration, encrypted private keys
+/// are decrypted before and encrypted again after the modification.
+fn modify_local_config<F>(local: &mut LocalConfig, f: F) -> Result<(), failure::Error>
+where
+    F: FnOnce(&mut LocalConfig) -> Result<(), failure::Error>,
+{
+    if local.is_locked() {
+        let passphrase = keystore::passphrase("Enter BTC anchoring private keys passphrase: ")?;
+        local.unlock(&passphrase)?;
+        f(local)?;
+        local.lock(&passphrase)
+    } else {
+        f(local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+    use serde_json::json;
+
+    use super::{config_change_proposal, proposed_config};
+    use crate::btc::gen_keypair;
+    use crate::config::GlobalConfig;
+
+    #[test]
+    fn test_config_change_proposal() {
+        let public_keys = (0..4)
+            .map(|_| gen_keypair(Network::Testnet).0)
+            .collect::<Vec<_>>();
+        let base_config =
+            GlobalConfig::with_public_keys(Network::Testnet, public_keys[0..3].to_vec()).unwrap();
+
+        assert!(proposed_config(base_config.clone(), public_keys.clone(), Some(5)).is_err());
+        let config = proposed_config(base_config.clone(), public_keys.clone(), Some(2)).unwrap();
+        assert_eq!(config.public_keys, public_keys);
+        assert_eq!(config.quorum(), 2);
+        assert_ne!(config.anchoring_address(), base_config.anchoring_address());
+
+        let actual_config = json!({
+            "hash": "f10f0c6c3a5d6e8dca1e2fb0fd4b6ea2cbba7f1c4f3c6f79cfa7d2ac6b1e3c9a",
+            "config": {
+                "previous_cfg_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+                "actual_from": 0,
+                "services": {
+                    "btc_anchoring": base_config,
+                    "configuration": null,
+                },
+            },
+        });
+        let proposal = config_change_proposal(&actual_config, &config, Some(100)).unwrap();
+        assert_eq!(proposal["previous_cfg_hash"], actual_config["hash"]);
+        assert_eq!(proposal["actual_from"], json!(100));
+        assert_eq!(
+            proposal["services"]["btc_anchoring"],
+            serde_json::to_value(&config).unwrap()
+        );
+        assert_eq!(proposal["services"]["configuration"], json!(null));
+    }
+}
@@ -29,13 +29,20 @@ use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 use crate::btc::{gen_keypair, PrivateKey, PublicKey};
-use crate::config::{Config, GlobalConfig, LocalConfig};
+use crate::config::{Config, EncryptedKeypair, GlobalConfig, LocalConfig};
+use crate::keystore::{self, EncryptedPrivateKey};
 use crate::rpc::{BitcoinRpcClient, BitcoinRpcConfig, BtcRelay};
+use crate::signer::AnchoringSigner;
+#[cfg(unix)]
+use crate::signer::RemoteSigner;
 use crate::{BtcAnchoringService, BTC_ANCHORING_SERVICE_NAME};
 
-use self::args::{Hash, NamedArgumentOptional, NamedArgumentRequired, TypedArgument};
+pub use self::keys::KeysCommand;
+
+use self::args::{Hash, NamedArgumentOptional, NamedArgumentRequired, Transaction, TypedArgument};
 
 mod args;
+mod keys;
 
 const BTC_ANCHORING_NETWORK: NamedArgumentRequired<Network> = NamedArgumentRequired {
     name: "btc_anchoring_network",
@@ -69,6 +76,15 @@ const BTC_ANCHORING_UTXO_CONFIRMATIONS: NamedArgumentRequired<u64> = NamedArgume
     default: Some(2),
 };
 
+const BTC_ANCHORING_QUORUM: NamedArgumentOptional<usize> = NamedArgumentOptional {
+    name: "btc_anchoring_quorum",
+    short_key: None,
+    long_key: "btc-anchoring-quorum",
+    help: "The number of signatures required to spend the anchoring output, \
+           by default the byzantine majority of validators is used.",
+    default: None,
+};
+
 struct GenerateCommonConfig;
 
 impl CommandExtension for GenerateCommonConfig {
@@ -78,6 +94,7 @@ impl CommandExtension for GenerateCommonConfig {
             BTC_ANCHORING_INTERVAL.to_argument(),
             BTC_ANCHORING_FEE.to_argument(),
             BTC_ANCHORING_UTXO_CONFIRMATIONS.to_argument(),
+            BTC_ANCHORING_QUORUM.to_argument(),
         ]
     }
 
@@ -95,6 +112,10 @@ impl CommandExtension for GenerateCommonConfig {
             ]
             .into_iter(),
         );
+        // Optional values are stored only if they are set.
+        if let Some(quorum) = BTC_ANCHORING_QUORUM.input_value(&context)? {
+            values.insert(BTC_ANCHORING_QUORUM.name(), toml::Value::try_from(quorum)?);
+        }
 
         context.set(keys::SERVICES_CONFIG, values);
         Ok(context)
@@ -127,12 +148,21 @@ const BTC_ANCHORING_RPC_PASSWORD: NamedArgumentOptional<String> = NamedArgumentO
     default: None,
 };
 
+const BTC_ANCHORING_NO_PASSWORD: &str = "btc_anchoring_no_password";
+
 impl CommandExtension for GenerateNodeConfig {
     fn args(&self) -> Vec<Argument> {
         vec![
             BTC_ANCHORING_RPC_HOST.to_argument(),
             BTC_ANCHORING_RPC_USERNAME.to_argument(),
             BTC_ANCHORING_RPC_PASSWORD.to_argument(),
+            Argument::new_flag(
+                BTC_ANCHORING_NO_PASSWORD,
+                "Don't encrypt the BTC anchoring private key with a passphrase.",
+                None,
+                "btc-anchoring-no-password",
+                false,
+            ),
         ]
     }
 
@@ -154,18 +184,26 @@ impl CommandExtension for GenerateNodeConfig {
             toml::Value::try_from(keypair.0)?,
         );
         services_secret_config.extend(
-            vec![
-                (
-                    "btc_anchoring_public_key".to_owned(),
-                    toml::Value::try_from(keypair.0)?,
-                ),
-                (
-                    "btc_anchoring_private_key".to_owned(),
-                    toml::Value::try_from(keypair.1)?,
-                ),
-            ]
+            vec![(
+                "btc_anchoring_public_key".to_owned(),
+                toml::Value::try_from(keypair.0)?,
+            )]
             .into_iter(),
         );
+        // Encrypts the private key unless the user explicitly refuses.
+        if context.has_flag(BTC_ANCHORING_NO_PASSWORD) {
+            services_secret_config.insert(
+                "btc_anchoring_private_key".to_owned(),
+                toml::Value::try_from(keypair.1)?,
+            );
+        } else {
+            let passphrase = keystore::passphrase("Enter BTC anchoring private key passphrase: ")?;
+            let encrypted_private_key = EncryptedPrivateKey::encrypt(&keypair.1, &passphrase)?;
+            services_secret_config.insert(
+                "btc_anchoring_encrypted_private_key".to_owned(),
+                toml::Value::try_from(encrypted_private_key)?,
+            );
+        }
 
         // Inserts RPC host.
         let host = BTC_ANCHORING_RPC_HOST.input_value(&context)?;
@@ -203,11 +241,20 @@ const BTC_ANCHORING_FUNDING_TXID: NamedArgumentOptional<Hash> = NamedArgumentOpt
     default: None,
 };
 
+const BTC_ANCHORING_FUNDING_TX_HEX: NamedArgumentOptional<Transaction> = NamedArgumentOptional {
+    name: "btc_anchoring_funding_tx_hex",
+    short_key: None,
+    long_key: "btc-anchoring-funding-tx-hex",
+    help: "Raw initial funding transaction, which is used without contacting the Bitcoin node",
+    default: None,
+};
+
 impl CommandExtension for Finalize {
     fn args(&self) -> Vec<Argument> {
         vec![
             BTC_ANCHORING_CREATE_FUNDING_TX.to_argument(),
             BTC_ANCHORING_FUNDING_TXID.to_argument(),
+            BTC_ANCHORING_FUNDING_TX_HEX.to_argument(),
         ]
     }
 
@@ -225,13 +272,24 @@ impl CommandExtension for Finalize {
         let fee = BTC_ANCHORING_FEE.output_value(&common_config.services_config)?;
         let confirmations =
             BTC_ANCHORING_UTXO_CONFIRMATIONS.output_value(&common_config.services_config)?;
+        let quorum: Option<usize> = common_config
+            .services_config
+            .get(BTC_ANCHORING_QUORUM.name)
+            .cloned()
+            .map(toml::Value::try_into)
+            .transpose()?;
 
         // Private part.
-        let private_key: PrivateKey = services_secret_config
+        let private_key: Option<PrivateKey> = services_secret_config
             .get("btc_anchoring_private_key")
-            .ok_or_else(|| format_err!("BTC private key not found"))?
-            .clone()
-            .try_into()?;
+            .cloned()
+            .map(toml::Value::try_into)
+            .transpose()?;
+        let encrypted_private_key: Option<EncryptedPrivateKey> = services_secret_config
+            .get("btc_anchoring_encrypted_private_key")
+            .cloned()
+            .map(toml::Value::try_into)
+            .transpose()?;
         let rpc_config: BitcoinRpcConfig = services_secret_config
             .get("btc_anchoring_rpc_config")
             .ok_or_else(|| format_err!("Bitcoin RPC configuration not found"))?
@@ -243,6 +301,9 @@ impl CommandExtension for Finalize {
         let funding_txid = BTC_ANCHORING_FUNDING_TXID
             .input_value(&context)?
             .map(|x| x.0);
+        let funding_tx_hex = BTC_ANCHORING_FUNDING_TX_HEX
+            .input_value(&context)?
+            .map(|x| x.0);
 
         // Gets anchoring public keys.
         let public_keys = {
@@ -261,10 +322,33 @@ impl CommandExtension for Finalize {
 
         // Creates global configuration.
         let mut global_config = GlobalConfig::with_public_keys(network, public_keys)?;
+        global_config.quorum = quorum;
+        global_config.try_redeem_script()?;
         // Generates initial funding transaction.
-        let relay = BitcoinRpcClient::from(rpc_config.clone());
         let addr = global_config.anchoring_address();
-        let funding_tx = if let Some(funding_txid) = funding_txid {
+        let funding_tx = if let Some(funding_tx) = funding_tx_hex {
+            ensure!(
+                funding_txid.is_none() && funding_tx_amount.is_none(),
+                "Raw funding transaction cannot be used along with the other funding options"
+            );
+            // The transaction is not checked in the Bitcoin network, so the operator is
+            // responsible for its confirmation.
+            let (_, output) = funding_tx.find_out(&addr.script_pubkey()).ok_or_else(|| {
+                format_err!(
+                    "Funding transaction {} doesn't pay to the anchoring address {}",
+                    funding_tx.id().to_hex(),
+                    addr
+                )
+            })?;
+            ensure!(
+                output.value > 0,
+                "Funding transaction {} pays zero value to the anchoring address {}",
+                funding_tx.id().to_hex(),
+                addr
+            );
+            funding_tx
+        } else if let Some(funding_txid) = funding_txid {
+            let relay = BitcoinRpcClient::from(rpc_config.clone());
             let info = relay.transaction_info(&funding_txid)?.ok_or_else(|| {
                 format_err!(
                     "Unable to find transaction with the given id {}",
@@ -281,6 +365,7 @@ impl CommandExtension for Finalize {
         } else {
             let satoshis = funding_tx_amount
                 .ok_or_else(|| format_err!("Expected `btc_anchoring_create_funding_tx` value"))?;
+            let relay = BitcoinRpcClient::from(rpc_config.clone());
             let transaction = relay.send_to_address(&addr.0, satoshis)?;
             println!("{}", transaction.id().to_hex());
             transaction
@@ -289,17 +374,29 @@ impl CommandExtension for Finalize {
         info!("BTC anchoring address is {}", addr);
 
         global_config.funding_transaction = Some(funding_tx);
+        global_config.validate()?;
         global_config.anchoring_interval = interval;
         global_config.transaction_fee = fee;
 
         // Creates local configuration.
-        let mut private_keys = HashMap::new();
-        private_keys.insert(addr, private_key);
-
-        let local_config = LocalConfig {
+        let mut local_config = LocalConfig {
             rpc: Some(rpc_config),
-            private_keys,
+            private_keys: HashMap::new(),
+            encrypted_private_keys: Vec::new(),
+            remote_signer: None,
+            pending_private_keys: Vec::new(),
+            encrypted_pending_private_keys: Vec::new(),
         };
+        if let Some(private_key) = encrypted_private_key {
+            local_config.encrypted_private_keys.push(EncryptedKeypair {
+                address: addr,
+                private_key,
+            });
+        } else {
+            let private_key =
+                private_key.ok_or_else(|| format_err!("BTC private key not found"))?;
+            local_config.private_keys.insert(addr, private_key);
+        }
 
         // Writes complete configuration to node_config.
         let config = Config {
@@ -335,7 +432,7 @@ impl ServiceFactory for BtcAnchoringFactory {
 
     fn make_service(&mut self, context: &Context) -> Box<dyn Service> {
         let node_config = context.get(keys::NODE_CONFIG).unwrap();
-        let btc_anchoring_config: Config = node_config
+        let mut btc_anchoring_config: Config = node_config
             .services_configs
             .get(BTC_ANCHORING_SERVICE_NAME)
             .expect("BTC anchoring config not found")
@@ -343,16 +440,45 @@ impl ServiceFactory for BtcAnchoringFactory {
             .try_into()
             .unwrap();
 
+        if btc_anchoring_config.local.remote_signer.is_none()
+            && btc_anchoring_config.local.is_locked()
+        {
+            let passphrase = keystore::passphrase("Enter BTC anchoring private keys passphrase: ")
+                .expect("Unable to read BTC anchoring private keys passphrase");
+            btc_anchoring_config
+                .local
+                .unlock(&passphrase)
+                .expect("Unable to decrypt BTC anchoring private keys");
+        }
+
         let btc_relay = btc_anchoring_config
             .local
             .rpc
             .map(BitcoinRpcClient::from)
             .map(Box::<dyn BtcRelay>::from);
-        let service = BtcAnchoringService::new(
-            btc_anchoring_config.global,
-            Arc::new(RwLock::new(btc_anchoring_config.local.private_keys)),
-            btc_relay,
-        );
+        let service = if let Some(socket_path) = btc_anchoring_config.local.remote_signer {
+            BtcAnchoringService::with_signer(
+                btc_anchoring_config.global,
+                remote_signer(socket_path),
+                btc_relay,
+            )
+        } else {
+            BtcAnchoringService::new(
+                btc_anchoring_config.global,
+                Arc::new(RwLock::new(btc_anchoring_config.local.private_keys)),
+                btc_relay,
+            )
+        };
         Box::new(service)
     }
 }
+
+#[cfg(unix)]
+fn remote_signer(socket_path: PathBuf) -> Box<dyn AnchoringSigner> {
+    Box::new(RemoteSigner::new(socket_path))
+}
+
+#[cfg(not(unix))]
+fn remote_signer(_socket_path: PathBuf) -> Box<dyn AnchoringSigner> {
+    panic!("Remote anchoring signer is supported only on Unix platforms")
+}
@@ -13,69 +13,94 @@
 // limitations under the License.
 
 use exonum::blockchain::ServiceContext;
-use exonum::helpers::ValidatorId;
+use exonum::crypto::Hash;
 
+use bitcoin::blockdata::transaction::TxIn;
+use btc_transaction_utils::multisig::RedeemScript;
 use btc_transaction_utils::p2wsh;
 use btc_transaction_utils::TxInRef;
 use failure::format_err;
-use log::trace;
+use log::{trace, warn};
 
 use std::cmp;
-use std::collections::HashMap;
 
 use crate::blockchain::data_layout::TxInputId;
-use crate::blockchain::transactions::TxSignature;
+use crate::blockchain::errors::ConfigurationError;
+use crate::blockchain::transactions::{TxCompactSignature, TxSignatureBatch, TxSweepSignature};
 use crate::blockchain::{BtcAnchoringSchema, BtcAnchoringState};
-use crate::btc::{Address, PrivateKey};
+use crate::btc::{Address, InputSignature, PublicKey, Transaction};
+use crate::config::SignerId;
 use crate::rpc::BtcRelay;
+use crate::signer::{AnchoringSigner, SignInputRequest};
 
 /// The goal of this task is to create anchoring transactions for the corresponding heights.
 pub struct UpdateAnchoringChainTask<'a> {
     context: &'a ServiceContext,
     anchoring_state: BtcAnchoringState,
-    private_keys: &'a HashMap<Address, PrivateKey>,
+    signer: &'a dyn AnchoringSigner,
 }
 
 impl<'a> UpdateAnchoringChainTask<'a> {
-    /// Creates the anchoring chain updater for the given context and private keys.
+    /// Creates the anchoring chain updater for the given context and anchoring signer
+    /// or returns an error if the anchoring service is misconfigured.
     pub fn new(
         context: &'a ServiceContext,
-        private_keys: &'a HashMap<Address, PrivateKey>,
-    ) -> UpdateAnchoringChainTask<'a> {
-        UpdateAnchoringChainTask {
+        signer: &'a dyn AnchoringSigner,
+    ) -> Result<UpdateAnchoringChainTask<'a>, ConfigurationError> {
+        Ok(UpdateAnchoringChainTask {
             context,
-            anchoring_state: BtcAnchoringSchema::new(context.snapshot()).actual_state(),
-            private_keys,
-        }
+            anchoring_state: BtcAnchoringSchema::new(context.snapshot()).try_actual_state()?,
+            signer,
+        })
     }
 
-    /// For validators this method creates an Exonum transaction with the signature for
+    /// For anchoring signers this method creates an Exonum transaction with the signature for
     /// the corresponding anchoring transaction if there is such a need.
     pub fn run(self) -> Result<(), failure::Error> {
-        if let Some(validator_id) = self.context.validator_id() {
-            let address = self.anchoring_state.output_address();
+        if self
+            .anchoring_state
+            .actual_configuration()
+            .is_paused(self.context.height())
+        {
+            trace!("Anchoring is paused.");
+            return Ok(());
+        }
+
+        if let Some(signer_id) = self.signer_id() {
+            let address = self
+                .anchoring_state
+                .actual_configuration()
+                .anchoring_address();
 
-            let private_key = self
-                .private_keys
-                .get(&address)
+            let public_key = self
+                .signer
+                .public_key(&address)?
                 .ok_or_else(|| format_err!("Private key for the address {} is absent.", address))?;
 
-            self.handle_as_validator(validator_id, &private_key)
+            self.handle_as_signer(signer_id, address, public_key)
         } else {
             self.handle_as_auditor()
         }
     }
 
-    fn handle_as_validator(
+    /// Returns the signer identifier of this node if it is one of the anchoring signers.
+    fn signer_id(&self) -> Option<SignerId> {
+        let config = self.anchoring_state.actual_configuration();
+        if config.signers.is_empty() {
+            self.context.validator_id().map(SignerId::from)
+        } else {
+            config.signer_id(self.context.public_key())
+        }
+    }
+
+    fn handle_as_signer(
         self,
-        validator_id: ValidatorId,
-        private_key: &PrivateKey,
+        signer_id: SignerId,
+        address: Address,
+        public_key: PublicKey,
     ) -> Result<(), failure::Error> {
         let schema = BtcAnchoringSchema::new(self.context.snapshot());
-        let latest_anchored_height = schema.latest_anchored_height();
-        let anchoring_height = self
-            .anchoring_state
-            .following_anchoring_height(latest_anchored_height);
+        let anchoring_height = schema.following_anchoring_height(&self.anchoring_state);
 
         if self.context.height() < anchoring_height {
             return Ok(());
@@ -91,45 +116,113 @@ impl<'a> UpdateAnchoringChainTask<'a> {
 
         let config = self.anchoring_state.actual_configuration();
         let redeem_script = config.redeem_script();
-        // Creates `Signature` transactions.
-        let pubkey = redeem_script.content().public_keys[validator_id.0 as usize];
-        let mut signer = p2wsh::InputSigner::new(redeem_script);
-
-        for (index, proposal_input) in proposal_inputs.iter().enumerate() {
-            let input_id = TxInputId::new(proposal.id(), index as u32);
+        // Creates `SignatureBatch` transaction.
+        let pubkey = *redeem_script
+            .content()
+            .public_keys
+            .get(signer_id.0 as usize)
+            .ok_or_else(|| format_err!("Public key of signer {} is absent.", signer_id))?;
+        if pubkey != public_key.0 {
+            return Err(format_err!(
+                "Anchoring signer key for the address {} doesn't match the key of signer {}.",
+                address,
+                signer_id
+            ));
+        }
 
-            if let Some(input_signatures) = schema.transaction_signatures().get(&input_id) {
-                if input_signatures.contains(validator_id) {
+        // Inputs may be locked by different redeem scripts, so the signer may have
+        // a different identifier in the redeem script of each input and should sign
+        // each input by the key of the address it spends. Inputs which the signer
+        // is unable to sign are skipped, so that they don't block the other ones.
+        let network = config.network;
+        let input_signers = proposal_inputs
+            .iter()
+            .enumerate()
+            .map(|(index, proposal_input)| {
+                let (redeem_script, input_signer_id) = schema.input_signer(
+                    &self.anchoring_state,
+                    &proposal,
+                    index,
+                    proposal_input,
+                    signer_id,
+                    self.context.public_key(),
+                );
+                if input_signer_id.is_none() {
                     trace!(
-                        " {:?} is already signed by validator {}",
-                        input_id,
-                        validator_id
+                        "Signer {} is absent in the redeem script of input {}.",
+                        signer_id,
+                        index
                     );
-                    continue;
                 }
-            }
+                let input_address: Address = p2wsh::address(&redeem_script, network).into();
+                input_signer_id.map(|id| (redeem_script, id, input_address))
+            })
+            .collect::<Vec<_>>();
 
-            let signature = signer.sign_input(
-                TxInRef::new(proposal.as_ref(), index),
-                proposal_inputs[index].as_ref(),
-                &private_key.0.key,
-            )?;
+        let already_signed = input_signers
+            .iter()
+            .enumerate()
+            .all(|(index, input_signer)| {
+                input_signer
+                    .as_ref()
+                    .map_or(true, |(_, input_signer_id, _)| {
+                        let input_id = TxInputId::new(proposal.id(), index as u32);
+                        schema
+                            .transaction_signatures()
+                            .get(&input_id)
+                            .map_or(false, |input_signatures| {
+                                input_signatures.contains(*input_signer_id)
+                            })
+                    })
+            });
+        if already_signed {
+            trace!(
+                " {} is already signed by signer {}",
+                proposal.id().to_hex(),
+                signer_id
+            );
+            return Ok(());
+        }
 
-            signer
-                .verify_input(
-                    TxInRef::new(proposal.as_ref(), index),
-                    proposal_input.as_ref(),
-                    &pubkey,
-                    &signature,
-                )
-                .unwrap();
-
-            self.context.broadcast_transaction(TxSignature {
-                validator: validator_id,
-                transaction: proposal.clone(),
-                input: index as u32,
-                input_signature: signature.into(),
+        let mut input_signatures = Vec::with_capacity(proposal_inputs.len());
+        for (index, proposal_input) in proposal_inputs.iter().enumerate() {
+            let (redeem_script, input_signer_id, input_address) = match &input_signers[index] {
+                Some(input_signer) => input_signer,
+                None => continue,
+            };
+            let signature = sign_input(
+                self.signer,
+                &proposal,
+                index,
+                proposal_input,
+                redeem_script,
+                *input_signer_id,
+                input_address,
+            );
+            match signature {
+                Ok(signature) => input_signatures.push((index, signature)),
+                Err(e) => warn!("Unable to sign input {}: {}", index, e),
+            }
+        }
+
+        if input_signatures.len() == proposal_inputs.len() {
+            self.context.broadcast_transaction(TxSignatureBatch {
+                signer: signer_id,
+                txid: proposal.id(),
+                input_signatures: input_signatures
+                    .into_iter()
+                    .map(|(_, signature)| signature)
+                    .collect(),
             });
+        } else {
+            for (index, input_signature) in input_signatures {
+                self.context.broadcast_transaction(TxCompactSignature {
+                    signer: signer_id,
+                    txid: proposal.id(),
+                    input: index as u32,
+                    input_signature,
+                });
+            }
         }
 
         Ok(())
@@ -141,7 +234,79 @@ impl<'a> UpdateAnchoringChainTask<'a> {
     }
 }
 
-/// The goal of this task is to push uncommitted anchoring transactions to the Bitcoin blockchain.
+/// The goal of this task is to sign the transactions which sweep the funds sent to
+/// the retired anchoring addresses if this node is a signer of these addresses.
+pub struct SweepRetiredFundsTask<'a> {
+    context: &'a ServiceContext,
+    signer: &'a dyn AnchoringSigner,
+}
+
+impl<'a> SweepRetiredFundsTask<'a> {
+    /// Creates the sweep task for the given context and anchoring signer.
+    pub fn new(
+        context: &'a ServiceContext,
+        signer: &'a dyn AnchoringSigner,
+    ) -> SweepRetiredFundsTask<'a> {
+        SweepRetiredFundsTask { context, signer }
+    }
+
+    /// Creates Exonum transactions with the signatures for the inputs of the proposed
+    /// sweep transaction, which spend the retired addresses this node has the keys for.
+    pub fn run(self) -> Result<(), failure::Error> {
+        let schema = BtcAnchoringSchema::new(self.context.snapshot());
+        let (proposal, proposal_inputs) = match schema.proposed_sweep_transaction() {
+            Some(proposal) => proposal?,
+            None => return Ok(()),
+        };
+
+        for (index, proposal_input) in proposal_inputs.iter().enumerate() {
+            let retired = match schema.sweep_input_configuration(&proposal, index, proposal_input) {
+                Some(retired) => retired,
+                None => continue,
+            };
+            let signer_id = match retired.signer_id(self.context.public_key()) {
+                Some(signer_id) => signer_id,
+                None => continue,
+            };
+            let input_id = TxInputId::new(proposal.id(), index as u32);
+            let is_signed = schema
+                .sweep_signatures()
+                .get(&input_id)
+                .map_or(false, |input_signatures| {
+                    input_signatures.contains(signer_id)
+                });
+            if is_signed {
+                continue;
+            }
+
+            let redeem_script = retired.configuration.redeem_script();
+            let address: Address =
+                p2wsh::address(&redeem_script, retired.configuration.network).into();
+            if self.signer.public_key(&address)?.is_none() {
+                trace!("Private key for the retired address {} is absent.", address);
+                continue;
+            }
+            let input_signature = sign_input(
+                self.signer,
+                &proposal,
+                index,
+                proposal_input,
+                &redeem_script,
+                signer_id,
+                &address,
+            )?;
+            self.context.broadcast_transaction(TxSweepSignature {
+                txid: proposal.id(),
+                input: index as u32,
+                input_signature,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The goal of this task is to push uncommitted anchoring and sweep transactions to the Bitcoin
+/// blockchain.
 #[derive(Debug)]
 pub struct SyncWithBtcRelayTask<'a> {
     context: &'a ServiceContext,
@@ -157,14 +322,23 @@ impl<'a> SyncWithBtcRelayTask<'a> {
     /// Performs anchoring transactions synchronization with the Bitcoin blockchain.
     /// That is, it finds the first uncommitted anchoring transaction in the Bitcoin
     /// blockchain and sequentially sends it and the subsequent ones to the Bitcoin mempool.
+    /// Sweep transactions are sent along with the anchoring transactions spending them.
     pub fn run(self) -> Result<(), failure::Error> {
         let schema = BtcAnchoringSchema::new(self.context.snapshot());
-        let sync_interval = cmp::max(1, schema.actual_configuration().anchoring_interval / 2);
+        let sync_interval = cmp::max(1, schema.try_actual_configuration()?.anchoring_interval / 2);
 
         if self.context.height().0 % sync_interval == 0 {
+            self.send_unspent_sweep_transactions();
             if let Some(index) = self.find_index_of_first_uncommitted_transaction()? {
                 let anchoring_txs = schema.anchoring_transactions_chain();
+                let sweep_txs = schema.sweep_transactions();
                 for tx in anchoring_txs.iter_from(index) {
+                    // Swept funds are spent by the anchoring transaction as the additional ones.
+                    for input in tx.0.input.iter().skip(1) {
+                        if let Some(sweep_tx) = sweep_txs.get(&spent_tx_id(input)) {
+                            self.send_sweep_transaction(&sweep_tx);
+                        }
+                    }
                     trace!(
                         "Send anchoring transaction to btc relay: {}",
                         tx.id().to_hex()
@@ -177,6 +351,39 @@ impl<'a> SyncWithBtcRelayTask<'a> {
         Ok(())
     }
 
+    /// Sends the finalized sweep transactions which are not spent by the anchoring
+    /// transactions yet. The spent ones are sent along with the uncommitted anchoring
+    /// transactions spending them, so the number of requests to the relay is bounded.
+    fn send_unspent_sweep_transactions(&self) {
+        let schema = BtcAnchoringSchema::new(self.context.snapshot());
+        for tx in schema.unspent_sweep_transactions() {
+            self.send_sweep_transaction(&tx);
+        }
+    }
+
+    /// Sends the sweep transaction if it is unknown to the Bitcoin network. Errors are
+    /// logged, so failure to send one sweep transaction doesn't affect the others.
+    fn send_sweep_transaction(&self, tx: &Transaction) {
+        match self.relay.transaction_info(&tx.id()) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                trace!("Send sweep transaction to btc relay: {}", tx.id().to_hex());
+                if let Err(e) = self.relay.send_transaction(tx) {
+                    warn!(
+                        "Unable to send sweep transaction {}: {}",
+                        tx.id().to_hex(),
+                        e
+                    );
+                }
+            }
+            Err(e) => warn!(
+                "Unable to get info of sweep transaction {}: {}",
+                tx.id().to_hex(),
+                e
+            ),
+        }
+    }
+
     fn find_index_of_first_uncommitted_transaction(&self) -> Result<Option<u64>, failure::Error> {
         let schema = BtcAnchoringSchema::new(self.context.snapshot());
         let anchoring_txs = schema.anchoring_transactions_chain();
@@ -196,3 +403,53 @@ impl<'a> SyncWithBtcRelayTask<'a> {
         Ok(None)
     }
 }
+
+/// Signs the given input of the proposed transaction by the key of the given address
+/// and verifies the produced signature.
+fn sign_input(
+    signer: &dyn AnchoringSigner,
+    proposal: &Transaction,
+    index: usize,
+    proposal_input: &Transaction,
+    redeem_script: &RedeemScript,
+    input_signer_id: SignerId,
+    input_address: &Address,
+) -> Result<InputSignature, failure::Error> {
+    let signature = signer.sign_input(&SignInputRequest {
+        address: input_address.clone(),
+        public_keys: redeem_script
+            .content()
+            .public_keys
+            .iter()
+            .cloned()
+            .map(PublicKey)
+            .collect(),
+        quorum: redeem_script.content().quorum,
+        transaction: proposal.clone(),
+        input: index,
+        prev_transaction: proposal_input.clone(),
+    })?;
+
+    p2wsh::InputSigner::new(redeem_script.clone())
+        .verify_input(
+            TxInRef::new(proposal.as_ref(), index),
+            proposal_input.as_ref(),
+            &redeem_script.content().public_keys[input_signer_id.0 as usize],
+            signature.as_ref(),
+        )
+        .map_err(|_| {
+            format_err!(
+                "Anchoring signer produced incorrect signature for input {}.",
+                index
+            )
+        })?;
+    Ok(signature)
+}
+
+/// Returns the identifier of the transaction whose output is spent by the given input.
+fn spent_tx_id(input: &TxIn) -> Hash {
+    let mut bytes = [0_u8; 32];
+    bytes.copy_from_slice(&input.previous_output.txid[..]);
+    bytes.reverse();
+    Hash::new(bytes)
+}
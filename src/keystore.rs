@@ -0,0 +1,94 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Password-based encryption of the anchoring private keys.
+
+use failure::format_err;
+use pwbox::{sodium::Sodium, ErasedPwBox, Eraser, Suite};
+use rand::thread_rng;
+use serde_derive::{Deserialize, Serialize};
+
+use std::env;
+use std::str::{self, FromStr};
+
+use crate::btc::PrivateKey;
+
+/// Name of the environment variable with the passphrase for the anchoring private keys.
+pub const PASSPHRASE_ENV_VAR: &str = "EXONUM_BTC_ANCHORING_PASSPHRASE";
+
+/// Private key encrypted with the passphrase using `scrypt` KDF and `xsalsa20-poly1305` cipher.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedPrivateKey(ErasedPwBox);
+
+impl EncryptedPrivateKey {
+    /// Encrypts the private key with the given passphrase.
+    pub fn encrypt(private_key: &PrivateKey, passphrase: &str) -> Result<Self, failure::Error> {
+        let wif = private_key.to_string();
+        let pwbox = Sodium::build_box(&mut thread_rng()).seal(passphrase, wif.as_bytes())?;
+        Ok(EncryptedPrivateKey(eraser().erase(&pwbox)?))
+    }
+
+    /// Decrypts the private key with the given passphrase.
+    pub fn decrypt(&self, passphrase: &str) -> Result<PrivateKey, failure::Error> {
+        let wif = eraser()
+            .restore(&self.0)?
+            .open(passphrase)
+            .map_err(|_| format_err!("Unable to decrypt private key, wrong passphrase"))?;
+        let wif = str::from_utf8(&wif)?;
+        Ok(PrivateKey::from_str(wif)?)
+    }
+}
+
+impl PartialEq for EncryptedPrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        serde_json::to_value(&self.0).ok() == serde_json::to_value(&other.0).ok()
+    }
+}
+
+/// Returns the passphrase for the anchoring private keys from the
+/// `EXONUM_BTC_ANCHORING_PASSPHRASE` environment variable or prompts it from the terminal.
+pub fn passphrase(prompt: &str) -> Result<String, failure::Error> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV_VAR) {
+        return Ok(passphrase);
+    }
+    Ok(rpassword::prompt_password_stdout(prompt)?)
+}
+
+fn eraser() -> Eraser {
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>();
+    eraser
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+
+    use super::EncryptedPrivateKey;
+    use crate::btc::gen_keypair;
+
+    #[test]
+    fn test_encrypted_private_key() {
+        let (_, private_key) = gen_keypair(Network::Testnet);
+
+        let encrypted = EncryptedPrivateKey::encrypt(&private_key, "correct horse").unwrap();
+        let decrypted = encrypted.decrypt("correct horse").unwrap();
+        assert_eq!(decrypted.to_string(), private_key.to_string());
+        assert!(encrypted.decrypt("battery staple").is_err());
+
+        let toml = ::toml::to_string(&encrypted).unwrap();
+        let encrypted2: EncryptedPrivateKey = ::toml::from_str(&toml).unwrap();
+        assert_eq!(encrypted2, encrypted);
+    }
+}
@@ -67,7 +67,7 @@ use log::{error, warn};
 mod handler;
 mod proto;
 
-pub use crate::factory::BtcAnchoringFactory as ServiceFactory;
+pub use crate::factory::{BtcAnchoringFactory as ServiceFactory, KeysCommand};
 pub use crate::service::{
     BtcAnchoringService, BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME,
 };
@@ -76,7 +76,9 @@ pub mod api;
 pub mod blockchain;
 pub mod btc;
 pub mod config;
+pub mod keystore;
 pub mod rpc;
+pub mod signer;
 pub mod test_helpers;
 
 pub(crate) mod factory;
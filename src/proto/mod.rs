@@ -18,15 +18,19 @@
 #![allow(bare_trait_objects)]
 #![allow(renamed_and_removed_lints)]
 
-pub use self::btc_anchoring::TxSignature;
+pub use self::btc_anchoring::{
+    TxAnchoringRequest, TxCompactSignature, TxSignature, TxSignatureBatch, TxSweepRequest,
+    TxSweepSignature,
+};
 
 use bitcoin;
 use btc_transaction_utils;
-use failure;
+use failure::{self, ensure};
 
-use exonum::proto::ProtobufConvert;
+use exonum::proto::{schema::*, ProtobufConvert};
 
 use crate::btc;
+use crate::config::SignerId;
 
 include!(concat!(env!("OUT_DIR"), "/protobuf_mod.rs"));
 
@@ -62,3 +66,19 @@ impl ProtobufConvert for btc::InputSignature {
         ))
     }
 }
+
+impl ProtobufConvert for SignerId {
+    type ProtoStruct = u32;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        u32::from(self.0)
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> Result<Self, failure::Error> {
+        ensure!(
+            pb <= u32::from(u16::max_value()),
+            "u32 is out of range for valid SignerId"
+        );
+        Ok(SignerId(pb as u16))
+    }
+}
@@ -17,9 +17,11 @@ use exonum::blockchain::{
     Schema as CoreSchema, Service, ServiceContext, Transaction, TransactionSet,
 };
 use exonum::crypto::Hash;
+use exonum::helpers::Height;
 use exonum::messages::RawTransaction;
 use exonum::storage::{Fork, Snapshot};
 
+use log::error;
 use serde_json::json;
 
 use std::sync::{Arc, RwLock};
@@ -30,8 +32,9 @@ use crate::api;
 use crate::blockchain::{BtcAnchoringSchema, Transactions};
 use crate::btc::{Address, PrivateKey};
 use crate::config::GlobalConfig;
-use crate::handler::{SyncWithBtcRelayTask, UpdateAnchoringChainTask};
+use crate::handler::{SweepRetiredFundsTask, SyncWithBtcRelayTask, UpdateAnchoringChainTask};
 use crate::rpc::BtcRelay;
+use crate::signer::{AnchoringSigner, LocalSigner};
 use crate::ResultEx;
 
 /// Anchoring service id.
@@ -44,7 +47,8 @@ pub(crate) type KeyPool = Arc<RwLock<HashMap<Address, PrivateKey>>>;
 /// Btc anchoring service implementation for the Exonum blockchain.
 pub struct BtcAnchoringService {
     global_config: GlobalConfig,
-    private_keys: KeyPool,
+    signer: Box<dyn AnchoringSigner>,
+    private_keys: Option<KeyPool>,
     btc_relay: Option<Box<dyn BtcRelay>>,
 }
 
@@ -61,9 +65,27 @@ impl BtcAnchoringService {
         private_keys: KeyPool,
         btc_relay: Option<Box<dyn BtcRelay>>,
     ) -> Self {
+        let signer = LocalSigner::from_key_pool(Arc::clone(&private_keys));
         Self {
             global_config,
-            private_keys,
+            signer: Box::new(signer),
+            private_keys: Some(private_keys),
+            btc_relay,
+        }
+    }
+
+    /// Creates a new btc anchoring service instance which signs anchoring transactions
+    /// by the given signer. The private API for the keys management is not available
+    /// for such instance.
+    pub fn with_signer(
+        global_config: GlobalConfig,
+        signer: Box<dyn AnchoringSigner>,
+        btc_relay: Option<Box<dyn BtcRelay>>,
+    ) -> Self {
+        Self {
+            global_config,
+            signer,
+            private_keys: None,
             btc_relay,
         }
     }
@@ -88,24 +110,63 @@ impl Service for BtcAnchoringService {
     }
 
     fn initialize(&self, _fork: &mut Fork) -> serde_json::Value {
+        if let Err(e) = self.global_config.validate() {
+            panic!("{}", e);
+        }
         json!(self.global_config)
     }
 
     fn before_commit(&self, fork: &mut Fork) {
         // Writes a hash of the latest block to the proof list index.
-        let block_header_hash = CoreSchema::new(&fork)
-            .block_hashes_by_height()
-            .last()
-            .expect("An attempt to invoke execute during the genesis block initialization.");
+        let (block_header_hash, current_height, is_configuration_changed) = {
+            let core_schema = CoreSchema::new(&fork);
+            let block_hashes = core_schema.block_hashes_by_height();
+            let block_header_hash = block_hashes
+                .last()
+                .expect("An attempt to invoke execute during the genesis block initialization.");
+            let current_height = Height(block_hashes.len());
+            // The following configuration becomes actual since the next block.
+            let is_configuration_changed = core_schema
+                .following_configuration()
+                .map_or(false, |config| config.actual_from == current_height.next());
+            (block_header_hash, current_height, is_configuration_changed)
+        };
 
         let mut schema = BtcAnchoringSchema::new(fork);
         schema.anchored_blocks_mut().push(block_header_hash);
+        if is_configuration_changed {
+            schema.clear_request_votes();
+        }
+        // Anchoring is suspended while the service is misconfigured.
+        if schema.try_actual_configuration().is_err() {
+            return;
+        }
+        // Removes signatures which are no longer needed. Signatures for the finalized
+        // transactions are stored in their witnesses.
+        let actual_proposal_id = schema
+            .actual_proposed_anchoring_transaction()
+            .and_then(Result::ok)
+            .map(|(proposal, _)| proposal.id());
+        schema.prune_transaction_signatures(actual_proposal_id);
+        schema.prune_anchoring_requests();
+        schema.prune_sweep_requests(current_height);
+        // Proposal becomes available for signing after the commit of the current block.
+        schema.update_proposal_heights(actual_proposal_id, current_height);
     }
 
     fn after_commit(&self, context: &ServiceContext) {
-        let keys = &self.private_keys.read().unwrap();
-        let task = UpdateAnchoringChainTask::new(context, keys);
+        let task = match UpdateAnchoringChainTask::new(context, self.signer.as_ref()) {
+            Ok(task) => task,
+            Err(e) => {
+                error!("Anchoring is suspended: {}", e);
+                return;
+            }
+        };
         task.run().log_error();
+        // Sweep transactions are signed independently of the anchoring ones.
+        SweepRetiredFundsTask::new(context, self.signer.as_ref())
+            .run()
+            .log_error();
         // TODO make this task async via tokio core or something else.
         if let Some(ref relay) = self.btc_relay.as_ref() {
             let task = SyncWithBtcRelayTask::new(context, relay.as_ref());
@@ -114,6 +175,6 @@ impl Service for BtcAnchoringService {
     }
 
     fn wire_api(&self, builder: &mut ServiceApiBuilder) {
-        api::wire(builder);
+        api::wire(builder, self.private_keys.clone());
     }
 }
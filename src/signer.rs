@@ -0,0 +1,310 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signers of the anchoring transactions inputs.
+//!
+//! The anchoring service does not sign the anchoring transactions by itself, it uses
+//! an [`AnchoringSigner`](trait.AnchoringSigner.html) implementation instead. The keys can
+//! be kept in the node process by the [`LocalSigner`](struct.LocalSigner.html) or in
+//! a separate signer daemon which is accessed by the [`RemoteSigner`](struct.RemoteSigner.html).
+//!
+//! The remote signer communicates with the daemon over a Unix socket. Each request is
+//! a single line with the JSON serialized [`SignerRequest`](enum.SignerRequest.html), the
+//! daemon replies with a single line with the JSON serialized
+//! [`SignerResponse`](enum.SignerResponse.html).
+
+use btc_transaction_utils::{
+    multisig::{RedeemScript, RedeemScriptBuilder, RedeemScriptError},
+    p2wsh, TxInRef,
+};
+use failure::format_err;
+use serde_derive::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+
+use crate::btc::{Address, InputSignature, PrivateKey, PublicKey, Transaction};
+use crate::service::KeyPool;
+
+#[cfg(unix)]
+pub use self::remote::{serve_connection, RemoteSigner};
+
+/// Context of the anchoring transaction input which is required to compute its signature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignInputRequest {
+    /// Anchoring address which identifies the signing key.
+    pub address: Address,
+    /// Public keys of the anchoring redeem script.
+    pub public_keys: Vec<PublicKey>,
+    /// Number of signatures required to spend the anchoring output.
+    pub quorum: usize,
+    /// Anchoring transaction to be signed.
+    pub transaction: Transaction,
+    /// Index of the signed input.
+    pub input: usize,
+    /// Transaction which output is spent by the signed input.
+    pub prev_transaction: Transaction,
+}
+
+impl SignInputRequest {
+    /// Returns the redeem script of the anchoring output spent by the signed input.
+    pub fn redeem_script(&self) -> Result<RedeemScript, RedeemScriptError> {
+        RedeemScriptBuilder::with_public_keys(self.public_keys.iter().map(|x| x.0))
+            .quorum(self.quorum)
+            .to_script()
+    }
+}
+
+/// Signer of the anchoring transactions inputs.
+pub trait AnchoringSigner: Send + Sync + Debug {
+    /// Returns the public key corresponding to the given anchoring address or `None`
+    /// if the signer has no private key for this address.
+    fn public_key(&self, address: &Address) -> Result<Option<PublicKey>, failure::Error>;
+    /// Signs the anchoring transaction input described by the given request.
+    fn sign_input(&self, request: &SignInputRequest) -> Result<InputSignature, failure::Error>;
+}
+
+/// Signer which keeps the private keys in the memory of the node process.
+#[derive(Debug, Clone)]
+pub struct LocalSigner {
+    private_keys: KeyPool,
+}
+
+impl LocalSigner {
+    /// Creates a signer with the given private keys for the anchoring addresses.
+    pub fn new(private_keys: HashMap<Address, PrivateKey>) -> Self {
+        Self::from_key_pool(Arc::new(RwLock::new(private_keys)))
+    }
+
+    pub(crate) fn from_key_pool(private_keys: KeyPool) -> Self {
+        Self { private_keys }
+    }
+}
+
+impl AnchoringSigner for LocalSigner {
+    fn public_key(&self, address: &Address) -> Result<Option<PublicKey>, failure::Error> {
+        let private_keys = self.private_keys.read().unwrap();
+        Ok(private_keys.get(address).map(PrivateKey::public_key))
+    }
+
+    fn sign_input(&self, request: &SignInputRequest) -> Result<InputSignature, failure::Error> {
+        let private_keys = self.private_keys.read().unwrap();
+        let private_key = private_keys.get(&request.address).ok_or_else(|| {
+            format_err!("Private key for the address {} is absent.", request.address)
+        })?;
+
+        let mut signer = p2wsh::InputSigner::new(request.redeem_script()?);
+        let signature = signer.sign_input(
+            TxInRef::new(request.transaction.as_ref(), request.input),
+            request.prev_transaction.as_ref(),
+            &private_key.0.key,
+        )?;
+        Ok(signature.into())
+    }
+}
+
+/// Request to the signer daemon.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum SignerRequest {
+    /// Requests the public key for the given anchoring address.
+    PublicKey {
+        /// Anchoring address.
+        address: Address,
+    },
+    /// Requests the signature of the anchoring transaction input.
+    SignInput(SignInputRequest),
+}
+
+/// Response of the signer daemon.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerResponse {
+    /// Public key for the requested anchoring address, if any.
+    PublicKey(Option<PublicKey>),
+    /// Signature of the requested input.
+    Signature(InputSignature),
+    /// Description of the error occurred during the request handling.
+    Error(String),
+}
+
+/// Handles the signer daemon request with the given signer.
+pub fn handle_request(signer: &dyn AnchoringSigner, request: SignerRequest) -> SignerResponse {
+    let response = match request {
+        SignerRequest::PublicKey { address } => {
+            signer.public_key(&address).map(SignerResponse::PublicKey)
+        }
+        SignerRequest::SignInput(request) => {
+            signer.sign_input(&request).map(SignerResponse::Signature)
+        }
+    };
+    response.unwrap_or_else(|e| SignerResponse::Error(e.to_string()))
+}
+
+#[cfg(unix)]
+mod remote {
+    use failure::{bail, ensure, format_err};
+
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::{handle_request, AnchoringSigner, SignInputRequest, SignerRequest, SignerResponse};
+    use crate::btc::{Address, InputSignature, PublicKey};
+
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Signer which sends requests to the separate signer daemon over a Unix socket.
+    #[derive(Debug, Clone)]
+    pub struct RemoteSigner {
+        socket_path: PathBuf,
+        timeout: Duration,
+    }
+
+    impl RemoteSigner {
+        /// Creates a signer for the daemon listening on the given Unix socket.
+        pub fn new<P: Into<PathBuf>>(socket_path: P) -> Self {
+            Self {
+                socket_path: socket_path.into(),
+                timeout: DEFAULT_TIMEOUT,
+            }
+        }
+
+        /// Sets the timeout of the daemon responses.
+        pub fn with_timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = timeout;
+            self
+        }
+
+        fn request(&self, request: &SignerRequest) -> Result<SignerResponse, failure::Error> {
+            let mut stream = UnixStream::connect(&self.socket_path).map_err(|e| {
+                format_err!(
+                    "Unable to connect to the anchoring signer at {}: {}",
+                    self.socket_path.display(),
+                    e
+                )
+            })?;
+            stream.set_read_timeout(Some(self.timeout))?;
+            stream.set_write_timeout(Some(self.timeout))?;
+
+            let mut line = serde_json::to_string(request)?;
+            line.push('\n');
+            stream.write_all(line.as_bytes())?;
+
+            let mut response = String::new();
+            BufReader::new(stream).read_line(&mut response)?;
+            ensure!(
+                !response.is_empty(),
+                "Anchoring signer closed the connection without response"
+            );
+            match serde_json::from_str(&response)? {
+                SignerResponse::Error(e) => bail!("Anchoring signer error: {}", e),
+                response => Ok(response),
+            }
+        }
+    }
+
+    impl AnchoringSigner for RemoteSigner {
+        fn public_key(&self, address: &Address) -> Result<Option<PublicKey>, failure::Error> {
+            let request = SignerRequest::PublicKey {
+                address: address.clone(),
+            };
+            match self.request(&request)? {
+                SignerResponse::PublicKey(public_key) => Ok(public_key),
+                response => bail!("Unexpected anchoring signer response: {:?}", response),
+            }
+        }
+
+        fn sign_input(&self, request: &SignInputRequest) -> Result<InputSignature, failure::Error> {
+            let request = SignerRequest::SignInput(request.clone());
+            match self.request(&request)? {
+                SignerResponse::Signature(signature) => Ok(signature),
+                response => bail!("Unexpected anchoring signer response: {:?}", response),
+            }
+        }
+    }
+
+    /// Serves the requests received over the given connection with the given signer
+    /// until the connection is closed by the other side.
+    pub fn serve_connection(
+        signer: &dyn AnchoringSigner,
+        stream: UnixStream,
+    ) -> Result<(), failure::Error> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let response = match serde_json::from_str(&line?) {
+                Ok(request) => handle_request(signer, request),
+                Err(e) => SignerResponse::Error(format!("Malformed request: {}", e)),
+            };
+            let mut line = serde_json::to_string(&response)?;
+            line.push('\n');
+            writer.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+    use maplit::hashmap;
+
+    use super::{AnchoringSigner, LocalSigner, SignerRequest, SignerResponse};
+    use crate::btc::gen_keypair;
+    use crate::config::GlobalConfig;
+
+    #[test]
+    fn test_local_signer_public_key() {
+        let (public_key, private_key) = gen_keypair(Network::Testnet);
+        let (other_public_key, _) = gen_keypair(Network::Testnet);
+        let config =
+            GlobalConfig::with_public_keys(Network::Testnet, vec![public_key, other_public_key])
+                .unwrap();
+        let address = config.anchoring_address();
+
+        let signer = LocalSigner::new(hashmap! { address.clone() => private_key });
+        assert_eq!(signer.public_key(&address).unwrap(), Some(public_key));
+
+        let other_address =
+            GlobalConfig::with_public_keys(Network::Testnet, vec![other_public_key])
+                .unwrap()
+                .anchoring_address();
+        assert_eq!(signer.public_key(&other_address).unwrap(), None);
+    }
+
+    #[test]
+    fn test_signer_messages_serde() {
+        let (public_key, _) = gen_keypair(Network::Testnet);
+        let address = GlobalConfig::with_public_keys(Network::Testnet, vec![public_key])
+            .unwrap()
+            .anchoring_address();
+
+        let request = SignerRequest::PublicKey { address };
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(json.contains(r#""method":"public_key""#));
+        assert_eq!(
+            serde_json::from_str::<SignerRequest>(&json).unwrap(),
+            request
+        );
+
+        let response = SignerResponse::PublicKey(Some(public_key));
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SignerResponse>(&json).unwrap(),
+            response
+        );
+    }
+}
@@ -16,4 +16,6 @@
 
 #[macro_use]
 pub mod rpc;
+#[cfg(unix)]
+pub mod signer;
 pub mod testkit;
@@ -0,0 +1,101 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Stub of the external anchoring signer daemon.
+
+use rand::{thread_rng, Rng};
+
+use std::env;
+use std::fs;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::btc::{Address, InputSignature, PublicKey};
+use crate::signer::{serve_connection, AnchoringSigner, RemoteSigner, SignInputRequest};
+use crate::ResultEx;
+
+/// Signer daemon which serves requests with the given signer in a separate thread.
+/// It also acts as the remote signer connected to this daemon.
+#[derive(Debug)]
+pub struct StubSigner {
+    remote: RemoteSigner,
+    socket_path: PathBuf,
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StubSigner {
+    /// Spawns a daemon with the given signer on a temporary Unix socket.
+    pub fn spawn<S: AnchoringSigner + 'static>(signer: S) -> Self {
+        let socket_path = env::temp_dir().join(format!(
+            "btc-anchoring-signer-{}-{}.sock",
+            process::id(),
+            thread_rng().gen::<u64>()
+        ));
+        let listener = UnixListener::bind(&socket_path).expect("Unable to bind signer socket");
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stopped = Arc::clone(&stopped);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        serve_connection(&signer, stream).log_error();
+                    }
+                }
+            })
+        };
+
+        Self {
+            remote: RemoteSigner::new(socket_path.clone()),
+            socket_path,
+            stopped,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns the path to the daemon socket.
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+}
+
+impl AnchoringSigner for StubSigner {
+    fn public_key(&self, address: &Address) -> Result<Option<PublicKey>, failure::Error> {
+        self.remote.public_key(address)
+    }
+
+    fn sign_input(&self, request: &SignInputRequest) -> Result<InputSignature, failure::Error> {
+        self.remote.sign_input(request)
+    }
+}
+
+impl Drop for StubSigner {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes up the listener thread to let it stop.
+        let _ = UnixStream::connect(&self.socket_path);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
@@ -24,7 +24,9 @@ use maplit::hashmap;
 use rand::{thread_rng, Rng, SeedableRng, StdRng};
 
 use exonum::api;
-use exonum::blockchain::{BlockProof, Blockchain, Schema as CoreSchema, StoredConfiguration};
+use exonum::blockchain::{
+    Block, BlockProof, Blockchain, Schema as CoreSchema, StoredConfiguration,
+};
 use exonum::crypto::{CryptoHash, Hash};
 use exonum::helpers::Height;
 use exonum::messages::{Message, RawTransaction, Signed};
@@ -37,13 +39,27 @@ use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 
+#[cfg(unix)]
+use crate::test_helpers::signer::StubSigner;
 use crate::{
-    api::{BlockHeaderProof, FindTransactionQuery, HeightQuery, PublicApi, TransactionProof},
-    blockchain::{transactions::TxSignature, BtcAnchoringSchema, BtcAnchoringState},
+    api::{
+        AddressQuery, AnchoredBlockProof, AnchoringSignersProof, AnchoringStatus, BitcoinKeypair,
+        BlockHeaderProof, FindTransactionQuery, HeightQuery, PrivateApi, PublicApi,
+        SignerStatsInfo, TransactionProof, TransitionInfo, TxidQuery,
+    },
+    blockchain::{
+        data_layout::AnchoringSigners,
+        transactions::{
+            TxAnchoringRequest, TxCompactSignature, TxSignature, TxSignatureBatch, TxSweepRequest,
+            TxSweepSignature,
+        },
+        BtcAnchoringSchema, BtcAnchoringState,
+    },
     btc,
-    config::{GlobalConfig, LocalConfig},
+    config::{GlobalConfig, LocalConfig, SignerId},
     rpc::BtcRelay,
     service::KeyPool,
+    signer::{AnchoringSigner, LocalSigner},
     test_helpers::rpc::*,
     BtcAnchoringService, BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME,
 };
@@ -103,6 +119,10 @@ pub fn gen_anchoring_config<R: Rng>(
         .map(|sk| LocalConfig {
             rpc: rpc.map(BtcRelay::config),
             private_keys: hashmap! { address.clone() => sk.clone() },
+            encrypted_private_keys: vec![],
+            remote_signer: None,
+            pending_private_keys: vec![],
+            encrypted_pending_private_keys: vec![],
         })
         .collect();
 
@@ -154,6 +174,7 @@ impl AnchoringTestKit {
         anchoring_interval: u64,
         mut rng: R,
         requests: Option<TestRequests>,
+        make_signer: Option<fn(LocalSigner) -> Box<dyn AnchoringSigner>>,
     ) -> Self {
         let network = Network::Testnet;
         let (global, locals) = gen_anchoring_config(
@@ -167,7 +188,12 @@ impl AnchoringTestKit {
 
         let local = locals[0].clone();
         let private_keys = Arc::new(RwLock::new(local.private_keys));
-        let service = BtcAnchoringService::new(global.clone(), Arc::clone(&private_keys), rpc);
+        let service = if let Some(make_signer) = make_signer {
+            let signer = make_signer(LocalSigner::from_key_pool(Arc::clone(&private_keys)));
+            BtcAnchoringService::with_signer(global.clone(), signer, rpc)
+        } else {
+            BtcAnchoringService::new(global.clone(), Arc::clone(&private_keys), rpc)
+        };
 
         let testkit = TestKitBuilder::validator()
             .with_service(service)
@@ -231,6 +257,7 @@ impl AnchoringTestKit {
             anchoring_interval,
             rng,
             Some(requests.clone()),
+            None,
         )
     }
 
@@ -246,6 +273,29 @@ impl AnchoringTestKit {
             anchoring_interval,
             rng,
             None,
+            None,
+        )
+    }
+
+    /// Same as [`new_without_rpc`](#method.new_without_rpc) but the anchoring transactions
+    /// are signed by the stub signer daemon through the remote signer.
+    #[cfg(unix)]
+    pub fn new_with_remote_signer(
+        validators_num: u16,
+        total_funds: u64,
+        anchoring_interval: u64,
+    ) -> Self {
+        let seed: &[_] = &[1, 2, 3, 9];
+        let rng: StdRng = SeedableRng::from_seed(seed);
+
+        Self::new(
+            None,
+            validators_num,
+            total_funds,
+            anchoring_interval,
+            rng,
+            None,
+            Some(|signer| Box::new(StubSigner::spawn(signer))),
         )
     }
 
@@ -325,6 +375,7 @@ impl AnchoringTestKit {
         &self,
         validators_num: u16,
     ) -> Result<Vec<Signed<RawTransaction>>, btc::BuilderError> {
+        let config = self.actual_anchoring_configuration();
         let validators = self
             .network()
             .validators()
@@ -333,52 +384,278 @@ impl AnchoringTestKit {
             .take(validators_num as usize);
 
         let mut signatures = Vec::new();
+        for validator in validators {
+            let signer_id = if config.signers.is_empty() {
+                validator.validator_id().unwrap().into()
+            } else if let Some(signer_id) = config.signer_id(validator.service_keypair().0) {
+                signer_id
+            } else {
+                continue;
+            };
+            signatures.extend(self.create_signature_tx(validator, signer_id)?);
+        }
+        Ok(signatures)
+    }
 
-        let redeem_script = self.redeem_script();
-        let mut signer = p2wsh::InputSigner::new(redeem_script.clone());
+    /// Creates the out-of-schedule anchoring requests for the block at the given height
+    /// on behalf of the given number of validators except for the `us` node.
+    pub fn create_anchoring_request_for_validators(
+        &self,
+        height: Height,
+        validators_num: u16,
+    ) -> Vec<Signed<RawTransaction>> {
+        self.network()
+            .validators()
+            .iter()
+            .filter(|v| v != &self.us())
+            .take(validators_num as usize)
+            .map(|validator| {
+                let (public_key, private_key) = validator.service_keypair();
+                Message::sign_transaction(
+                    TxAnchoringRequest { height: height.0 },
+                    BTC_ANCHORING_SERVICE_ID,
+                    *public_key,
+                    &private_key,
+                )
+            })
+            .collect()
+    }
 
-        for validator in validators {
-            let validator_id = validator.validator_id().unwrap();
-            let (public_key, private_key) = validator.service_keypair();
+    /// Creates the requests to sweep the funds of the given transaction sent to the retired
+    /// anchoring addresses on behalf of the given number of validators except for the `us` node.
+    pub fn create_sweep_request_for_validators(
+        &self,
+        transaction: btc::Transaction,
+        validators_num: u16,
+    ) -> Vec<Signed<RawTransaction>> {
+        self.network()
+            .validators()
+            .iter()
+            .filter(|v| v != &self.us())
+            .take(validators_num as usize)
+            .map(|validator| {
+                let (public_key, private_key) = validator.service_keypair();
+                Message::sign_transaction(
+                    TxSweepRequest {
+                        transaction: transaction.clone(),
+                    },
+                    BTC_ANCHORING_SERVICE_ID,
+                    *public_key,
+                    &private_key,
+                )
+            })
+            .collect()
+    }
 
-            let schema = BtcAnchoringSchema::new(self.snapshot());
+    /// Creates signature transactions for the proposed sweep transaction on behalf of
+    /// the given number of validators except for the `us` node.
+    ///
+    /// Validators sign only the inputs which spend the retired addresses they were
+    /// the anchoring signers of.
+    pub fn create_sweep_signature_tx_for_validators(
+        &self,
+        validators_num: u16,
+    ) -> Result<Vec<Signed<RawTransaction>>, btc::BuilderError> {
+        let schema = BtcAnchoringSchema::new(self.snapshot());
+        let (proposal, proposal_inputs) = match schema.proposed_sweep_transaction() {
+            Some(p) => p?,
+            None => return Ok(Vec::new()),
+        };
 
-            if let Some(p) = schema.actual_proposed_anchoring_transaction() {
-                let (proposal, proposal_inputs) = p?;
+        let validators = self
+            .network()
+            .validators()
+            .iter()
+            .filter(|v| v != &self.us())
+            .take(validators_num as usize);
 
-                let address = schema.actual_state().output_address();
+        let mut signatures = Vec::new();
+        for validator in validators {
+            let (public_key, private_key) = validator.service_keypair();
+            for (index, proposal_input) in proposal_inputs.iter().enumerate() {
+                let retired = schema
+                    .sweep_input_configuration(&proposal, index, proposal_input)
+                    .unwrap();
+                let signer_id = match retired.signer_id(public_key) {
+                    Some(signer_id) => signer_id,
+                    None => continue,
+                };
+                let redeem_script = retired.configuration.redeem_script();
+                let address: btc::Address =
+                    p2wsh::address(&redeem_script, retired.configuration.network).into();
+                // Node configurations are generated in the order of the anchoring public keys.
                 let btc_private_key =
-                    &self.node_configs[validator_id.0 as usize].private_keys[&address];
-
-                for (index, proposal_input) in proposal_inputs.iter().enumerate() {
-                    let signature = signer
-                        .sign_input(
-                            TxInRef::new(proposal.as_ref(), index),
-                            proposal_input.as_ref(),
-                            &btc_private_key.0.key,
-                        )
-                        .unwrap();
-
-                    let tx = Message::sign_transaction(
-                        TxSignature {
-                            validator: validator_id,
-                            transaction: proposal.clone(),
-                            input: index as u32,
-                            input_signature: signature.into(),
-                        },
-                        BTC_ANCHORING_SERVICE_ID,
-                        *public_key,
-                        &private_key,
-                    );
-                    signatures.push(tx);
-                }
+                    &self.node_configs[signer_id.0 as usize].private_keys[&address];
+                let input_signature = p2wsh::InputSigner::new(redeem_script)
+                    .sign_input(
+                        TxInRef::new(proposal.as_ref(), index),
+                        proposal_input.as_ref(),
+                        &btc_private_key.0.key,
+                    )
+                    .unwrap()
+                    .into();
+                signatures.push(Message::sign_transaction(
+                    TxSweepSignature {
+                        txid: proposal.id(),
+                        input: index as u32,
+                        input_signature,
+                    },
+                    BTC_ANCHORING_SERVICE_ID,
+                    *public_key,
+                    &private_key,
+                ));
             }
         }
         Ok(signatures)
     }
 
+    /// Creates signature transactions for the actual proposed anchoring transaction
+    /// on behalf of the given validator with the given signer identifier.
+    pub fn create_signature_tx(
+        &self,
+        validator: &TestNode,
+        signer_id: SignerId,
+    ) -> Result<Vec<Signed<RawTransaction>>, btc::BuilderError> {
+        let (public_key, private_key) = validator.service_keypair();
+
+        let signatures =
+            if let Some((proposal, input_signatures)) = self.sign_proposal(validator, signer_id)? {
+                input_signatures
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, input_signature)| {
+                        Message::sign_transaction(
+                            TxCompactSignature {
+                                signer: signer_id,
+                                txid: proposal.id(),
+                                input: index as u32,
+                                input_signature,
+                            },
+                            BTC_ANCHORING_SERVICE_ID,
+                            *public_key,
+                            &private_key,
+                        )
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+        Ok(signatures)
+    }
+
+    /// Creates signature transactions in the legacy format, which embeds the whole
+    /// anchoring transaction, on behalf of the given validator with the given signer identifier.
+    pub fn create_legacy_signature_tx(
+        &self,
+        validator: &TestNode,
+        signer_id: SignerId,
+    ) -> Result<Vec<Signed<RawTransaction>>, btc::BuilderError> {
+        let (public_key, private_key) = validator.service_keypair();
+
+        let signatures =
+            if let Some((proposal, input_signatures)) = self.sign_proposal(validator, signer_id)? {
+                input_signatures
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, input_signature)| {
+                        Message::sign_transaction(
+                            TxSignature {
+                                signer: signer_id,
+                                transaction: proposal.clone(),
+                                input: index as u32,
+                                input_signature,
+                            },
+                            BTC_ANCHORING_SERVICE_ID,
+                            *public_key,
+                            &private_key,
+                        )
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+        Ok(signatures)
+    }
+
+    /// Creates a signature batch transaction for the actual proposed anchoring transaction
+    /// on behalf of the given validator with the given signer identifier.
+    pub fn create_signature_batch_tx(
+        &self,
+        validator: &TestNode,
+        signer_id: SignerId,
+    ) -> Result<Option<Signed<RawTransaction>>, btc::BuilderError> {
+        let (public_key, private_key) = validator.service_keypair();
+
+        let batch = self
+            .sign_proposal(validator, signer_id)?
+            .map(|(proposal, input_signatures)| {
+                Message::sign_transaction(
+                    TxSignatureBatch {
+                        signer: signer_id,
+                        txid: proposal.id(),
+                        input_signatures,
+                    },
+                    BTC_ANCHORING_SERVICE_ID,
+                    *public_key,
+                    &private_key,
+                )
+            });
+        Ok(batch)
+    }
+
+    /// Signs all inputs of the actual proposed anchoring transaction by the Bitcoin key
+    /// of the given validator acting as the signer with the given identifier.
+    fn sign_proposal(
+        &self,
+        validator: &TestNode,
+        signer_id: SignerId,
+    ) -> Result<Option<(btc::Transaction, Vec<btc::InputSignature>)>, btc::BuilderError> {
+        let schema = BtcAnchoringSchema::new(self.snapshot());
+
+        let (proposal, proposal_inputs) = match schema.actual_proposed_anchoring_transaction() {
+            Some(p) => p?,
+            None => return Ok(None),
+        };
+
+        let state = schema.actual_state();
+        let network = state.actual_configuration().network;
+        // Node configurations are generated in the order of the anchoring public keys.
+        let private_keys = &self.node_configs[signer_id.0 as usize].private_keys;
+
+        let input_signatures = proposal_inputs
+            .iter()
+            .enumerate()
+            .map(|(index, proposal_input)| {
+                let (redeem_script, _) = schema.input_signer(
+                    &state,
+                    &proposal,
+                    index,
+                    proposal_input,
+                    signer_id,
+                    validator.service_keypair().0,
+                );
+                // Each input is signed by the key of the address it spends.
+                let address: btc::Address = p2wsh::address(&redeem_script, network).into();
+                let btc_private_key = &private_keys[&address];
+                p2wsh::InputSigner::new(redeem_script)
+                    .sign_input(
+                        TxInRef::new(proposal.as_ref(), index),
+                        proposal_input.as_ref(),
+                        &btc_private_key.0.key,
+                    )
+                    .unwrap()
+                    .into()
+            })
+            .collect();
+        Ok(Some((proposal, input_signatures)))
+    }
+
     /// Creates a configuration change proposal which excludes
     /// one of validators from the consensus.
+    ///
+    /// The funding transaction is removed from the proposed anchoring configuration,
+    /// since it pays to the previous anchoring address and would make the configuration
+    /// invalid.
     pub fn drop_validator_proposal(&mut self) -> TestNetworkConfiguration {
         let mut proposal = self.configuration_change_proposal();
         let mut validators = proposal.validators().to_vec();
@@ -394,6 +671,7 @@ impl AnchoringTestKit {
 
         let service_configuration = GlobalConfig {
             public_keys: keys,
+            funding_transaction: None,
             ..config
         };
         proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
@@ -445,6 +723,57 @@ impl PublicApi for TestKitApi {
             .query(&query)
             .get("v1/block_header_proof")
     }
+
+    fn anchored_block(
+        &self,
+        query: HeightQuery,
+    ) -> Result<Option<AnchoredBlockProof>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .query(&query)
+            .get("v1/anchored_block")
+    }
+
+    fn anchoring_signers(&self, query: TxidQuery) -> Result<AnchoringSignersProof, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .query(&query)
+            .get("v1/transaction/signers")
+    }
+
+    fn signers_stats(&self, _query: ()) -> Result<Vec<SignerStatsInfo>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/validators/stats")
+    }
+
+    fn status(&self, _query: ()) -> Result<AnchoringStatus, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/status")
+    }
+
+    fn transition(&self, _query: ()) -> Result<Option<TransitionInfo>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/transition")
+    }
+}
+
+impl PrivateApi for TestKitApi {
+    type Error = api::Error;
+
+    fn private_keys(&self, _query: ()) -> Result<Vec<btc::Address>, Self::Error> {
+        self.private(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/private_keys")
+    }
+
+    fn add_private_key(&self, keypair: BitcoinKeypair) -> Result<(), Self::Error> {
+        self.private(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .query(&keypair)
+            .post("v1/private_keys")
+    }
+
+    fn remove_private_key(&self, query: AddressQuery) -> Result<(), Self::Error> {
+        self.private(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .query(&query)
+            .post("v1/private_keys/remove")
+    }
 }
 
 fn validate_table_proof(
@@ -524,3 +853,53 @@ impl ValidateProof for BlockHeaderProof {
         Ok((values[0].0, *values[0].1))
     }
 }
+
+impl ValidateProof for AnchoredBlockProof {
+    type Output = (Block, btc::Transaction);
+
+    fn validate(self, actual_config: &StoredConfiguration) -> Result<Self::Output, failure::Error> {
+        let (_, tx) = self.anchoring_transaction.validate(actual_config)?;
+        let payload = tx
+            .anchoring_payload()
+            .ok_or_else(|| format_err!("Anchoring transaction doesn't contain payload"))?;
+        ensure!(
+            payload.block_height == self.block.height(),
+            "Block height doesn't match"
+        );
+        ensure!(
+            payload.block_hash == self.block.hash(),
+            "Block hash doesn't match"
+        );
+        if let Some(state_hash) = payload.state_hash {
+            ensure!(
+                &state_hash == self.block.state_hash(),
+                "State hash doesn't match"
+            );
+        }
+        Ok((self.block, tx))
+    }
+}
+
+impl ValidateProof for AnchoringSignersProof {
+    type Output = Option<AnchoringSigners>;
+
+    fn validate(self, actual_config: &StoredConfiguration) -> Result<Self::Output, failure::Error> {
+        let proof_entry =
+            validate_table_proof(actual_config, &self.latest_authorized_block, self.to_table)?;
+        let table_location = Blockchain::service_table_unique_key(BTC_ANCHORING_SERVICE_ID, 4);
+        ensure!(proof_entry.0 == table_location, "Invalid table location");
+        // Validates value.
+        let checked_proof = self
+            .to_signers
+            .check()
+            .map_err(|e| format_err!("An error occurred {:?}", e))?;
+        ensure!(
+            checked_proof.merkle_root() == proof_entry.1,
+            "Signers table hash doesn't match"
+        );
+        Ok(checked_proof
+            .entries()
+            .map(|(_, signers)| signers.clone())
+            .next())
+    }
+}
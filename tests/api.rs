@@ -11,12 +11,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use exonum::{helpers::Height, storage::Snapshot};
+use bitcoin::network::constants::Network;
+use exonum::{crypto::hash, helpers::Height, storage::Snapshot};
 use exonum_btc_anchoring::{
-    api::{FindTransactionQuery, HeightQuery, PublicApi},
+    api::{
+        AddressQuery, AnchoringStatus, BitcoinKeypair, FindTransactionQuery, HeightQuery,
+        PrivateApi, PublicApi, TransitionInfo, TxidQuery,
+    },
     blockchain::BtcAnchoringSchema,
     btc,
-    config::GlobalConfig,
+    config::{GlobalConfig, SignerId},
     test_helpers::testkit::{AnchoringTestKit, ValidateProof},
     BTC_ANCHORING_SERVICE_NAME,
 };
@@ -105,6 +109,120 @@ fn following_address() {
     );
 }
 
+#[test]
+fn status() {
+    let validators_num = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 150000, 4);
+    let address = anchoring_testkit.anchoring_address();
+    assert_eq!(
+        anchoring_testkit.api().status(NULL_QUERY).unwrap(),
+        AnchoringStatus::Regular {
+            address: address.clone()
+        }
+    );
+
+    let mut proposal = anchoring_testkit.drop_validator_proposal();
+    let service_config: GlobalConfig = proposal.service_config(BTC_ANCHORING_SERVICE_NAME);
+    let following_address = service_config.anchoring_address();
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_block();
+
+    assert_eq!(
+        anchoring_testkit.api().status(NULL_QUERY).unwrap(),
+        AnchoringStatus::Transition {
+            address,
+            following_address,
+        }
+    );
+}
+
+#[test]
+fn transition() {
+    let validators_num = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 150000, 4);
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(4)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+    // There is no transition for regular anchoring.
+    assert_eq!(
+        anchoring_testkit.api().transition(NULL_QUERY).unwrap(),
+        None
+    );
+
+    let address = anchoring_testkit.anchoring_address();
+    let mut proposal = anchoring_testkit.drop_validator_proposal();
+    let service_config: GlobalConfig = proposal.service_config(BTC_ANCHORING_SERVICE_NAME);
+    let following_address = service_config.anchoring_address();
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_block();
+
+    let expected_info = TransitionInfo {
+        actual_address: address,
+        following_address,
+        transition_transaction: None,
+        actual_from: Height(16),
+        missing_funds: None,
+    };
+    assert_eq!(
+        anchoring_testkit.api().transition(NULL_QUERY).unwrap(),
+        Some(expected_info.clone())
+    );
+
+    // Signs the transition transaction.
+    anchoring_testkit.renew_address();
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(4)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    let transition_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(
+        anchoring_testkit.api().transition(NULL_QUERY).unwrap(),
+        Some(TransitionInfo {
+            transition_transaction: Some(transition_tx.id()),
+            ..expected_info
+        })
+    );
+}
+
+#[test]
+fn transition_insufficient_funds() {
+    let validators_num = 5;
+    // Single transaction fee is about 15000.
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 20000, 4);
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let mut proposal = anchoring_testkit.drop_validator_proposal();
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_block();
+
+    let info = anchoring_testkit
+        .api()
+        .transition(NULL_QUERY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.transition_transaction, None);
+    let missing_funds = info.missing_funds.unwrap();
+    assert!(missing_funds > 0);
+
+    assert_eq!(
+        anchoring_testkit.api().status(NULL_QUERY).unwrap(),
+        AnchoringStatus::TransitionBlocked {
+            address: info.actual_address,
+            following_address: info.following_address,
+            missing_funds,
+        }
+    );
+}
+
 #[test]
 fn find_transaction_regular() {
     let validators_num = 4;
@@ -297,3 +415,237 @@ fn block_header_proof() {
     assert_eq!(value.0, 4);
     assert_eq!(value.1, anchoring_testkit.block_hash_on_height(Height(4)));
 }
+
+// Tries to get the header of an anchored block which state hash is committed by the payload.
+#[test]
+fn anchored_block() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    // Anchors block on height 0 without state hash.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    // Enables state hash anchoring.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        anchor_state_hash: true,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(3));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    // Anchors block on height 4 with state hash.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_block();
+
+    let api = anchoring_testkit.api();
+    let cfg = anchoring_testkit.actual_configuration();
+
+    let (block, tx) = api
+        .anchored_block(HeightQuery { height: 0 })
+        .unwrap()
+        .unwrap()
+        .validate(&cfg)
+        .unwrap();
+    assert_eq!(block.height(), Height(0));
+    assert_eq!(tx.anchoring_payload().unwrap().state_hash, None);
+
+    let (block, tx) = api
+        .anchored_block(HeightQuery { height: 4 })
+        .unwrap()
+        .unwrap()
+        .validate(&cfg)
+        .unwrap();
+    assert_eq!(block.height(), Height(4));
+    assert_eq!(
+        tx.anchoring_payload().unwrap().state_hash,
+        Some(*block.state_hash())
+    );
+
+    // Block on height 1 is not anchored.
+    assert!(api
+        .anchored_block(HeightQuery { height: 1 })
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn anchoring_signers() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let api = anchoring_testkit.api();
+    let cfg = anchoring_testkit.actual_configuration();
+    let signers = api
+        .anchoring_signers(TxidQuery { txid: tx0.id() })
+        .unwrap()
+        .validate(&cfg)
+        .unwrap()
+        .unwrap();
+    let signer_ids = signers.signers();
+    assert_eq!(signer_ids.len(), 3);
+    assert!(signer_ids.contains(&SignerId(1)));
+    assert!(signer_ids.contains(&SignerId(2)));
+    assert!(signers.finalization_height > Height(0));
+    assert!(signers.finalization_height <= anchoring_testkit.height());
+
+    // Checks the proof of absence.
+    let unknown = api
+        .anchoring_signers(TxidQuery {
+            txid: hash(&[1, 2, 3]),
+        })
+        .unwrap()
+        .validate(&cfg)
+        .unwrap();
+    assert!(unknown.is_none());
+}
+
+#[test]
+fn signers_stats() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    // The last validator never signs anchoring transactions.
+    for &height in &[4, 8] {
+        let signatures = anchoring_testkit
+            .create_signature_tx_for_validators(2)
+            .unwrap();
+        anchoring_testkit.create_block_with_transactions(signatures);
+        anchoring_testkit.create_blocks_until(Height(height));
+    }
+    assert_eq!(
+        btc_anchoring_schema(&anchoring_testkit)
+            .anchoring_transactions_chain()
+            .len(),
+        2
+    );
+
+    let stats = anchoring_testkit.api().signers_stats(NULL_QUERY).unwrap();
+    assert_eq!(stats.len(), 4);
+    for info in &stats[0..3] {
+        assert_eq!(info.stats.signed, 2);
+        assert_eq!(info.stats.missed, 0);
+        assert!(info.average_latency.unwrap() >= 1.0);
+    }
+    assert_eq!(stats[3].signer, SignerId(3));
+    assert_eq!(stats[3].stats.signed, 0);
+    assert_eq!(stats[3].stats.missed, 2);
+    assert_eq!(stats[3].average_latency, None);
+}
+
+#[test]
+fn private_keys() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    let address = anchoring_testkit.anchoring_address();
+    let private_key = anchoring_testkit.anchoring_us().1.private_keys[&address].clone();
+
+    let api = anchoring_testkit.api();
+    assert_eq!(api.private_keys(NULL_QUERY).unwrap(), vec![address.clone()]);
+    api.remove_private_key(AddressQuery {
+        address: address.clone(),
+    })
+    .unwrap();
+    assert!(api.private_keys(NULL_QUERY).unwrap().is_empty());
+
+    // Without the key of the `us` node the quorum is not reached.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+    assert!(anchoring_testkit.last_anchoring_tx().is_none());
+
+    // Keys of other signers are rejected.
+    let other_private_key = anchoring_testkit.node_configs[1].private_keys[&address].clone();
+    assert!(api
+        .add_private_key(BitcoinKeypair {
+            address: address.clone(),
+            private_key: other_private_key,
+        })
+        .is_err());
+    // Unknown addresses are rejected.
+    let (public_key, other_private_key) = btc::gen_keypair(Network::Testnet);
+    let other_address = GlobalConfig::with_public_keys(Network::Testnet, vec![public_key])
+        .unwrap()
+        .anchoring_address();
+    assert!(api
+        .add_private_key(BitcoinKeypair {
+            address: other_address,
+            private_key: other_private_key,
+        })
+        .is_err());
+    assert!(api.private_keys(NULL_QUERY).unwrap().is_empty());
+
+    api.add_private_key(BitcoinKeypair {
+        address: address.clone(),
+        private_key,
+    })
+    .unwrap();
+    assert_eq!(api.private_keys(NULL_QUERY).unwrap(), vec![address]);
+
+    // The `us` node signs the anchoring transaction with the restored key.
+    anchoring_testkit.create_block();
+    anchoring_testkit.create_block();
+    assert!(anchoring_testkit.last_anchoring_tx().is_some());
+}
+
+#[test]
+fn private_keys_of_retired_address() {
+    let validators_num = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 150000, 4);
+    let retired_address = anchoring_testkit.anchoring_address();
+    let private_key = anchoring_testkit.anchoring_us().1.private_keys[&retired_address].clone();
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let mut proposal = anchoring_testkit.drop_validator_proposal();
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(7));
+    anchoring_testkit.renew_address();
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(17));
+    assert_ne!(anchoring_testkit.anchoring_address(), retired_address);
+
+    let api = anchoring_testkit.api();
+    api.remove_private_key(AddressQuery {
+        address: retired_address.clone(),
+    })
+    .unwrap();
+    assert!(!api
+        .private_keys(NULL_QUERY)
+        .unwrap()
+        .contains(&retired_address));
+
+    // The key of the retired address is needed to sweep the funds sent to it.
+    api.add_private_key(BitcoinKeypair {
+        address: retired_address.clone(),
+        private_key,
+    })
+    .unwrap();
+    assert!(api
+        .private_keys(NULL_QUERY)
+        .unwrap()
+        .contains(&retired_address));
+}
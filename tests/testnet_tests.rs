@@ -12,18 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use exonum::blockchain::TransactionErrorType;
+use bitcoin::network::constants::Network;
+use exonum::blockchain::{TransactionErrorType, TransactionSet};
+use exonum::crypto;
 use exonum::explorer::BlockWithTransactions;
-use exonum::helpers::Height;
+use exonum::helpers::{Height, ValidatorId};
+use exonum::messages::Message;
 use exonum_btc_anchoring::{
-    blockchain::errors::ErrorCode,
-    btc::BuilderError,
-    config::GlobalConfig,
+    api::{AnchoringStatus, PublicApi},
+    blockchain::{
+        data_layout::TxInputId,
+        errors::ErrorCode,
+        transactions::{Transactions, TxAnchoringRequest},
+        BtcAnchoringSchema, SWEEP_REQUEST_LIFETIME,
+    },
+    btc::{self, BuilderError},
+    config::{CatchUpPolicy, GlobalConfig, SignerId},
     test_helpers::testkit::{create_fake_funding_transaction, AnchoringTestKit},
-    BTC_ANCHORING_SERVICE_NAME,
+    BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME,
 };
 
 use matches::assert_matches;
+use serde_json::json;
 
 fn assert_tx_error(block: BlockWithTransactions, e: ErrorCode) {
     assert_eq!(
@@ -32,6 +42,15 @@ fn assert_tx_error(block: BlockWithTransactions, e: ErrorCode) {
     );
 }
 
+fn validator_service_keys(testkit: &AnchoringTestKit) -> Vec<crypto::PublicKey> {
+    testkit
+        .network()
+        .validators()
+        .iter()
+        .map(|validator| *validator.service_keypair().0)
+        .collect()
+}
+
 #[test]
 fn simple() {
     let validators_num = 4;
@@ -287,6 +306,189 @@ fn insufficient_funds_during_address_change() {
     );
 }
 
+#[test]
+fn insufficient_funds_during_address_change_with_new_funding_tx() {
+    let validators_num = 5;
+    // single tx fee is ~ 15000
+    let initial_sum = 20000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // removing one of validators
+    let mut proposal = anchoring_testkit.drop_validator_proposal();
+    let mut service_config: GlobalConfig = proposal.service_config(BTC_ANCHORING_SERVICE_NAME);
+
+    // additional funding
+    let new_address = service_config.anchoring_address();
+    let new_funding_tx = create_fake_funding_transaction(&new_address, 150000);
+
+    service_config.funding_transaction = Some(new_funding_tx.clone());
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_config);
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+
+    anchoring_testkit.create_blocks_until(Height(7));
+
+    anchoring_testkit.renew_address();
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(10));
+
+    // new funding transaction should be consumed during creation of transition tx
+    let tx_transition = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_ne!(tx0, tx_transition);
+    assert_eq!(tx_transition.0.input.len(), 2);
+    assert_eq!(tx_transition.prev_tx_id(), tx0.id());
+    assert_eq!(
+        tx_transition.0.input[1].previous_output.txid,
+        new_funding_tx.0.txid()
+    );
+    assert_eq!(
+        tx_transition.anchoring_metadata().unwrap().0,
+        &new_address.script_pubkey()
+    );
+    assert!(BtcAnchoringSchema::new(anchoring_testkit.snapshot())
+        .spent_funding_transactions()
+        .contains(&new_funding_tx.id()));
+
+    // anchoring continues with the new address
+    anchoring_testkit.create_blocks_until(Height(17));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(20));
+
+    let tx_changed = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx_changed.prev_tx_id(), tx_transition.id());
+    assert_eq!(tx_changed.0.input.len(), 1);
+}
+
+#[test]
+fn insufficient_funds_during_keys_rotation() {
+    let validators_num = 5;
+    // single tx fee is ~ 15000
+    let initial_sum = 20000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // rotating the anchoring keys of all validators
+    let keypairs = (0..validators_num)
+        .map(|_| btc::gen_keypair(Network::Testnet))
+        .collect::<Vec<_>>();
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let mut service_config: GlobalConfig = proposal.service_config(BTC_ANCHORING_SERVICE_NAME);
+    service_config.public_keys = keypairs.iter().map(|(public_key, _)| *public_key).collect();
+
+    // additional funding
+    let new_address = service_config.anchoring_address();
+    let new_funding_tx = create_fake_funding_transaction(&new_address, 150000);
+
+    service_config.funding_transaction = Some(new_funding_tx.clone());
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_config);
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+
+    // validators keep both the actual and the rotated keys
+    let us = anchoring_testkit.us().validator_id().unwrap().0 as usize;
+    for (index, (_, private_key)) in keypairs.into_iter().enumerate() {
+        if index == us {
+            anchoring_testkit
+                .local_private_keys
+                .write()
+                .unwrap()
+                .insert(new_address.clone(), private_key.clone());
+        }
+        anchoring_testkit.node_configs[index]
+            .private_keys
+            .insert(new_address.clone(), private_key);
+    }
+
+    anchoring_testkit.create_blocks_until(Height(7));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(10));
+
+    // new funding transaction is spent by the rotated keys of the actual signers
+    let tx_transition = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx_transition.0.input.len(), 2);
+    assert_eq!(tx_transition.prev_tx_id(), tx0.id());
+    assert_eq!(
+        tx_transition.0.input[1].previous_output.txid,
+        new_funding_tx.0.txid()
+    );
+
+    // anchoring continues with the rotated keys
+    anchoring_testkit.create_blocks_until(Height(17));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(20));
+
+    let tx_changed = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx_changed.prev_tx_id(), tx_transition.id());
+}
+
+#[test]
+fn insufficient_funds_during_signers_change() {
+    let validators_num = 4;
+    // single tx fee is ~ 15000
+    let initial_sum = 20000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    // the following signers don't hold any keys of the actual ones
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let mut service_config: GlobalConfig = proposal.service_config(BTC_ANCHORING_SERVICE_NAME);
+    service_config.public_keys = (0..validators_num)
+        .map(|_| btc::gen_keypair(Network::Testnet).0)
+        .collect();
+    service_config.signers = (0..validators_num)
+        .map(|_| crypto::gen_keypair().0)
+        .collect();
+    let new_address = service_config.anchoring_address();
+    service_config.funding_transaction =
+        Some(create_fake_funding_transaction(&new_address, 150000));
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_config);
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(7));
+
+    // the following funding transaction is not spent, so the transition is blocked
+    assert_matches!(
+        BtcAnchoringSchema::new(anchoring_testkit.snapshot())
+            .actual_proposed_anchoring_transaction(),
+        Some(Err(BuilderError::InsufficientFunds { .. }))
+    );
+    assert_matches!(
+        anchoring_testkit.api().status(()).unwrap(),
+        AnchoringStatus::TransitionBlocked { .. }
+    );
+}
+
 #[test]
 fn signature_while_paused_in_transition() {
     let validators_num = 5;
@@ -442,3 +644,848 @@ fn broken_anchoring_recovery() {
             > recovery_tx.anchoring_payload().unwrap().block_height
     );
 }
+
+#[test]
+fn explicit_signers() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signers = validator_service_keys(&anchoring_testkit);
+
+    // Reverse the order of validators, so that validator identifiers no longer
+    // coincide with the signer ones.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let mut validators = proposal.validators().to_vec();
+    validators.reverse();
+    proposal.set_validators(validators);
+
+    let service_configuration = GlobalConfig {
+        signers,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(2));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    // The validator tries to sign with its validator identifier instead of the signer one.
+    let validator = anchoring_testkit.network().validators()[1].clone();
+    assert_eq!(validator.validator_id(), Some(ValidatorId(1)));
+    let forged_signatures = anchoring_testkit
+        .create_signature_tx(&validator, SignerId(1))
+        .unwrap();
+    let block = anchoring_testkit.create_block_with_transactions(forged_signatures);
+    assert_tx_error(block, ErrorCode::UnauthorizedSigner);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    let tx0_meta = tx0.anchoring_metadata().unwrap();
+    assert!(tx0_meta.1.block_height == Height(0));
+}
+
+#[test]
+fn signature_from_wrong_validator() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    // The validator tries to sign on behalf of another validator.
+    let validator = anchoring_testkit.network().validators()[1].clone();
+    let forged_signatures = anchoring_testkit
+        .create_signature_tx(&validator, SignerId(2))
+        .unwrap();
+    let block = anchoring_testkit.create_block_with_transactions(forged_signatures);
+    assert_tx_error(block, ErrorCode::ValidatorMismatch);
+}
+
+#[test]
+fn signature_batch() {
+    let validators_num = 4;
+    let initial_sum = 50000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    // Adds the second input to the next anchoring transaction.
+    let address = anchoring_testkit.anchoring_address();
+    let new_funding_tx = create_fake_funding_transaction(&address, initial_sum);
+
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        funding_transaction: Some(new_funding_tx),
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(6));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    let batches = anchoring_testkit.network().validators()[1..3]
+        .iter()
+        .map(|validator| {
+            let signer_id = validator.validator_id().unwrap().into();
+            anchoring_testkit
+                .create_signature_batch_tx(validator, signer_id)
+                .unwrap()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let block = anchoring_testkit.create_block_with_transactions(batches);
+    assert!(block[0].status().is_ok());
+    assert!(block[1].status().is_ok());
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.0.input.len(), 2);
+    assert_eq!(tx1.anchoring_metadata().unwrap().1.block_height, Height(4));
+}
+
+#[test]
+fn compact_signature_tx() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let validator = anchoring_testkit.network().validators()[1].clone();
+    let signatures = anchoring_testkit
+        .create_signature_tx(&validator, SignerId(1))
+        .unwrap();
+    assert_eq!(signatures.len(), 1);
+    assert_matches!(
+        Transactions::tx_from_raw(signatures[0].payload().clone()).unwrap(),
+        Transactions::CompactSignature(_)
+    );
+
+    let proposal_txid = BtcAnchoringSchema::new(anchoring_testkit.snapshot())
+        .actual_proposed_anchoring_transaction()
+        .unwrap()
+        .unwrap()
+        .0
+        .id();
+    let block = anchoring_testkit.create_block_with_transactions(signatures);
+    assert!(block[0].status().is_ok());
+    assert!(BtcAnchoringSchema::new(anchoring_testkit.snapshot())
+        .transaction_signatures()
+        .contains(&TxInputId::new(proposal_txid, 0)));
+}
+
+#[test]
+fn legacy_signature_tx() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit.network().validators()[1..3]
+        .iter()
+        .flat_map(|validator| {
+            let signer_id = validator.validator_id().unwrap().into();
+            anchoring_testkit
+                .create_legacy_signature_tx(validator, signer_id)
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let block = anchoring_testkit.create_block_with_transactions(signatures);
+    assert!(block[0].status().is_ok());
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_metadata().unwrap().1.block_height, Height(0));
+}
+
+#[test]
+fn transaction_signatures_pruning() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let proposal_id = |testkit: &AnchoringTestKit| {
+        BtcAnchoringSchema::new(testkit.snapshot())
+            .actual_proposed_anchoring_transaction()
+            .unwrap()
+            .unwrap()
+            .0
+            .id()
+    };
+    let signed_txids = |testkit: &AnchoringTestKit| {
+        BtcAnchoringSchema::new(testkit.snapshot())
+            .transaction_signatures()
+            .keys()
+            .map(|input_id| input_id.txid)
+            .collect::<Vec<_>>()
+    };
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(1)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let abandoned_proposal_id = proposal_id(&anchoring_testkit);
+    assert!(signed_txids(&anchoring_testkit).contains(&abandoned_proposal_id));
+
+    // Changes the fee, so the signed proposal is abandoned.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        transaction_fee: 20,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(3));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let actual_proposal_id = proposal_id(&anchoring_testkit);
+    assert_ne!(actual_proposal_id, abandoned_proposal_id);
+    assert!(signed_txids(&anchoring_testkit)
+        .iter()
+        .all(|txid| txid == &actual_proposal_id));
+
+    // Finalizes the actual proposal.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.id(), actual_proposal_id);
+    assert!(!signed_txids(&anchoring_testkit).contains(&actual_proposal_id));
+}
+
+#[test]
+fn invalid_configuration_ignored() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    let actual_configuration = anchoring_testkit.actual_anchoring_configuration();
+
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        anchoring_interval: 0,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    assert!(service_configuration.validate().is_err());
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(3));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    // The invalid configuration is not considered as the following one.
+    assert!(BtcAnchoringSchema::new(anchoring_testkit.snapshot())
+        .following_configuration()
+        .is_none());
+    anchoring_testkit.create_blocks_until(Height(4));
+    assert_eq!(
+        anchoring_testkit.actual_anchoring_configuration(),
+        GlobalConfig {
+            signers: validator_service_keys(&anchoring_testkit),
+            ..actual_configuration
+        }
+    );
+
+    // Anchoring continues with the latest valid configuration.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(8));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_metadata().unwrap().1.block_height, Height(0));
+}
+
+#[test]
+fn invalid_configuration_keeps_validators() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    let signers = validator_service_keys(&anchoring_testkit);
+
+    // The validators are reordered along with the invalid anchoring configuration.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let mut validators = proposal.validators().to_vec();
+    validators.reverse();
+    proposal.set_validators(validators);
+    let service_configuration = GlobalConfig {
+        anchoring_interval: 0,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(2));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    // Signers are still identified in the order of the previous validators.
+    assert_eq!(
+        anchoring_testkit.actual_anchoring_configuration().signers,
+        signers
+    );
+    let validator = anchoring_testkit.network().validators()[1].clone();
+    let forged_signatures = anchoring_testkit
+        .create_signature_tx(&validator, SignerId(1))
+        .unwrap();
+    let block = anchoring_testkit.create_block_with_transactions(forged_signatures);
+    assert_tx_error(block, ErrorCode::UnauthorizedSigner);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_metadata().unwrap().1.block_height, Height(0));
+}
+
+#[test]
+fn misconfigured_anchoring() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    let actual_configuration = anchoring_testkit.actual_anchoring_configuration();
+
+    // The malformed configuration is not replaced by the previous one.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, json!({ "network": "testnet" }));
+    proposal.set_actual_from(Height(2));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    assert_matches!(
+        anchoring_testkit.api().status(()).unwrap(),
+        AnchoringStatus::Misconfigured { .. }
+    );
+    assert!(BtcAnchoringSchema::new(anchoring_testkit.snapshot())
+        .actual_proposed_anchoring_transaction()
+        .is_none());
+    let requests = anchoring_testkit.create_anchoring_request_for_validators(Height(1), 1);
+    let block = anchoring_testkit.create_block_with_transactions(requests);
+    assert_tx_error(block, ErrorCode::Misconfigured);
+
+    // Anchoring is resumed by the valid configuration.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, actual_configuration);
+    proposal.set_actual_from(Height(6));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    assert_matches!(
+        anchoring_testkit.api().status(()).unwrap(),
+        AnchoringStatus::Regular { .. }
+    );
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(8));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_metadata().unwrap().1.block_height, Height(0));
+}
+
+#[test]
+fn paused_anchoring() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let leftover_signature = anchoring_testkit
+        .create_signature_tx_for_validators(1)
+        .unwrap()
+        .remove(0);
+
+    // Pauses anchoring, so the missed heights will be skipped after the pause.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        paused_until_height: Some(Height(14)),
+        catch_up_policy: CatchUpPolicy::LatestHeight,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(6));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(8));
+
+    let block = anchoring_testkit.create_block_with_transactions(vec![leftover_signature]);
+    assert_tx_error(block, ErrorCode::Paused);
+    assert!(anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap()
+        .is_empty());
+
+    // Blocks are still recorded during the pause.
+    let anchored_blocks_len = |testkit: &AnchoringTestKit| {
+        BtcAnchoringSchema::new(testkit.snapshot())
+            .anchored_blocks()
+            .len()
+    };
+    let len_before = anchored_blocks_len(&anchoring_testkit);
+    anchoring_testkit.create_blocks_until(Height(14));
+    assert_eq!(anchored_blocks_len(&anchoring_testkit), len_before + 5);
+    assert_eq!(anchoring_testkit.last_anchoring_tx().unwrap(), tx0);
+
+    // Anchoring resumes from the latest height.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+    assert_eq!(tx1.anchoring_metadata().unwrap().1.block_height, Height(12));
+}
+
+#[test]
+fn catch_up_latest_height() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        catch_up_policy: CatchUpPolicy::LatestHeight,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(3));
+    anchoring_testkit.commit_configuration_change(proposal);
+
+    // Anchoring falls behind for several intervals.
+    anchoring_testkit.create_blocks_until(Height(17));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    // The missed intervals are skipped with the single anchoring transaction.
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+    assert_eq!(tx1.anchoring_metadata().unwrap().1.block_height, Height(16));
+}
+
+#[test]
+fn anchoring_request_by_validators() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 10);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(5));
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let is_requested = |testkit: &AnchoringTestKit| {
+        BtcAnchoringSchema::new(testkit.snapshot())
+            .anchoring_requests()
+            .values()
+            .any(|height| height == 3)
+    };
+
+    // The request is not approved without the byzantine majority.
+    let mut requests = anchoring_testkit.create_anchoring_request_for_validators(Height(3), 3);
+    let last_request = requests.pop().unwrap();
+    anchoring_testkit.create_block_with_transactions(requests);
+    assert!(!is_requested(&anchoring_testkit));
+
+    let block = anchoring_testkit.create_block_with_transactions(vec![last_request]);
+    assert!(block[0].status().is_ok());
+    assert!(is_requested(&anchoring_testkit));
+
+    // The requested height is anchored out of schedule.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+    assert_eq!(tx1.anchoring_metadata().unwrap().1.block_height, Height(3));
+    assert!(!is_requested(&anchoring_testkit));
+
+    // The anchored height cannot be requested again.
+    let requests = anchoring_testkit.create_anchoring_request_for_validators(Height(3), 1);
+    let block = anchoring_testkit.create_block_with_transactions(requests);
+    assert_tx_error(block, ErrorCode::IncorrectHeight);
+
+    // The schedule goes on.
+    anchoring_testkit.create_blocks_until(Height(11));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx2 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx2.prev_tx_id(), tx1.id());
+    assert_eq!(tx2.anchoring_metadata().unwrap().1.block_height, Height(10));
+}
+
+#[test]
+fn anchoring_request_votes_cleared_on_configuration_change() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 10);
+    anchoring_testkit.create_blocks_until(Height(5));
+
+    let schema = |testkit: &AnchoringTestKit| BtcAnchoringSchema::new(testkit.snapshot());
+    let requests = anchoring_testkit.create_anchoring_request_for_validators(Height(3), 2);
+    anchoring_testkit.create_block_with_transactions(requests);
+    assert_eq!(
+        schema(&anchoring_testkit)
+            .anchoring_request_votes()
+            .values()
+            .map(|votes| votes.count())
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+
+    // Votes refer to the validator identifiers, so they are removed along with
+    // the configuration change.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        transaction_fee: 20,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(8));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(8));
+    assert!(schema(&anchoring_testkit)
+        .anchoring_request_votes()
+        .keys()
+        .next()
+        .is_none());
+
+    // The previous votes are not counted.
+    let mut requests = anchoring_testkit.create_anchoring_request_for_validators(Height(3), 3);
+    let last_request = requests.pop().unwrap();
+    anchoring_testkit.create_block_with_transactions(vec![last_request]);
+    assert!(schema(&anchoring_testkit)
+        .anchoring_requests()
+        .keys()
+        .next()
+        .is_none());
+}
+
+#[test]
+fn anchoring_request_by_admin() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 10);
+    let (admin_key, admin_secret_key) = crypto::gen_keypair();
+    let request = |height: u64| {
+        Message::sign_transaction(
+            TxAnchoringRequest { height },
+            BTC_ANCHORING_SERVICE_ID,
+            admin_key,
+            &admin_secret_key,
+        )
+    };
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    // The request from the unknown key is rejected.
+    anchoring_testkit.create_blocks_until(Height(3));
+    let block = anchoring_testkit.create_block_with_transactions(vec![request(2)]);
+    assert_tx_error(block, ErrorCode::UnauthorizedRequest);
+
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        admin_key: Some(admin_key),
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(5));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    // The request from the administrator is approved at once.
+    let block = anchoring_testkit.create_block_with_transactions(vec![request(6)]);
+    assert!(block[0].status().is_ok());
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.anchoring_metadata().unwrap().1.block_height, Height(6));
+}
+
+#[cfg(unix)]
+#[test]
+fn remote_signer() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_with_remote_signer(validators_num, 70000, 4);
+
+    // Signature of the `us` node is produced by the stub signer daemon,
+    // so the quorum is reached only if the remote signer works.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_metadata().unwrap().1.block_height, Height(0));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(8));
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.id(), tx1.prev_tx_id());
+    assert_eq!(tx1.anchoring_metadata().unwrap().1.block_height, Height(4));
+}
+
+#[test]
+fn sweep_retired_address() {
+    let validators_num = 5;
+    let initial_sum = 150000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+    let retired_address = anchoring_testkit.anchoring_address();
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    // removing one of validators
+    let mut proposal = anchoring_testkit.drop_validator_proposal();
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(7));
+    anchoring_testkit.renew_address();
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(17));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(20));
+
+    let actual_address = anchoring_testkit.anchoring_address();
+    assert_ne!(actual_address, retired_address);
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(
+        tx0.anchoring_metadata().unwrap().0,
+        &actual_address.script_pubkey()
+    );
+
+    // Funds sent to the actual address cannot be swept.
+    let actual_funding_tx = create_fake_funding_transaction(&actual_address, 10000);
+    let requests = anchoring_testkit.create_sweep_request_for_validators(actual_funding_tx, 1);
+    let block = anchoring_testkit.create_block_with_transactions(requests);
+    assert_tx_error(block, ErrorCode::UnsuitableSweepTransaction);
+
+    // Anchoring transactions which pay to the retired address are already spent.
+    let retired_anchoring_tx = BtcAnchoringSchema::new(anchoring_testkit.snapshot())
+        .anchoring_transactions_chain()
+        .get(0)
+        .unwrap();
+    assert_eq!(
+        retired_anchoring_tx.anchoring_metadata().unwrap().0,
+        &retired_address.script_pubkey()
+    );
+    let requests = anchoring_testkit.create_sweep_request_for_validators(retired_anchoring_tx, 1);
+    let block = anchoring_testkit.create_block_with_transactions(requests);
+    assert_tx_error(block, ErrorCode::UnsuitableSweepTransaction);
+
+    // The sweep request is approved by the byzantine majority of validators.
+    let stray_tx = create_fake_funding_transaction(&retired_address, 50000);
+    let is_requested = |testkit: &AnchoringTestKit| {
+        BtcAnchoringSchema::new(testkit.snapshot())
+            .sweep_requests()
+            .contains(&stray_tx.id())
+    };
+    let mut requests = anchoring_testkit.create_sweep_request_for_validators(stray_tx.clone(), 3);
+    let last_request = requests.pop().unwrap();
+    anchoring_testkit.create_block_with_transactions(requests);
+    assert!(!is_requested(&anchoring_testkit));
+
+    let block = anchoring_testkit.create_block_with_transactions(vec![last_request]);
+    assert!(block[0].status().is_ok());
+    assert!(is_requested(&anchoring_testkit));
+
+    // Sweeping doesn't hold up the anchoring.
+    anchoring_testkit.create_blocks_until(Height(25));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+    assert_eq!(tx1.0.input.len(), 1);
+    assert!(is_requested(&anchoring_testkit));
+
+    // The stray output is swept by the separate transaction signed by the retired signers.
+    let signatures = anchoring_testkit
+        .create_sweep_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    let sweep_tx = schema.sweep_transactions().values().next().unwrap();
+    assert_eq!(sweep_tx.0.input.len(), 1);
+    assert_eq!(sweep_tx.0.input[0].previous_output.txid, stray_tx.0.txid());
+    assert_eq!(sweep_tx.0.output.len(), 1);
+    assert_eq!(
+        sweep_tx.0.output[0].script_pubkey,
+        actual_address.script_pubkey()
+    );
+    assert!(sweep_tx.0.output[0].value < 50000);
+    assert!(schema.spent_funding_transactions().contains(&stray_tx.id()));
+    assert!(!is_requested(&anchoring_testkit));
+
+    // The swept funds are spent by the next anchoring transaction.
+    anchoring_testkit.create_blocks_until(Height(28));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx2 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx2.prev_tx_id(), tx1.id());
+    assert_eq!(tx2.0.input.len(), 2);
+    assert_eq!(tx2.0.input[1].previous_output.txid, sweep_tx.0.txid());
+    assert!(tx2.unspent_value().unwrap() > tx1.unspent_value().unwrap());
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert!(schema.spent_funding_transactions().contains(&sweep_tx.id()));
+    assert!(schema.unspent_sweep_transactions().is_empty());
+
+    anchoring_testkit.create_blocks_until(Height(32));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx3 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx3.prev_tx_id(), tx2.id());
+    assert_eq!(tx3.0.input.len(), 1);
+
+    // The swept transaction cannot be requested again.
+    let requests = anchoring_testkit.create_sweep_request_for_validators(stray_tx, 1);
+    let block = anchoring_testkit.create_block_with_transactions(requests);
+    assert_tx_error(block, ErrorCode::UnsuitableSweepTransaction);
+}
+
+#[test]
+fn sweep_retired_address_by_retired_signers() {
+    let validators_num = 5;
+    let initial_sum = 150000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+    let retired_address = anchoring_testkit.anchoring_address();
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    // The anchoring is handed over to the part of validators with the new keys,
+    // so the `us` node and the first validator are the retired signers only.
+    let keypairs = (0..3)
+        .map(|_| btc::gen_keypair(Network::Testnet))
+        .collect::<Vec<_>>();
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let mut service_config: GlobalConfig = proposal.service_config(BTC_ANCHORING_SERVICE_NAME);
+    service_config.public_keys = keypairs.iter().map(|(public_key, _)| *public_key).collect();
+    service_config.signers = proposal.validators()[2..]
+        .iter()
+        .map(|validator| *validator.service_keypair().0)
+        .collect();
+    service_config.funding_transaction = None;
+    let actual_address = service_config.anchoring_address();
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_config);
+    proposal.set_actual_from(Height(16));
+    anchoring_testkit.commit_configuration_change(proposal);
+
+    // Node configurations are looked up by the signer identifiers.
+    for (index, (_, private_key)) in keypairs.into_iter().enumerate() {
+        anchoring_testkit.node_configs[index]
+            .private_keys
+            .insert(actual_address.clone(), private_key);
+    }
+
+    anchoring_testkit.create_blocks_until(Height(7));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(17));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(4)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(20));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(
+        tx0.anchoring_metadata().unwrap().0,
+        &actual_address.script_pubkey()
+    );
+
+    // The sweep request expires if the retired signers don't reach the quorum.
+    let stray_tx = create_fake_funding_transaction(&retired_address, 50000);
+    let is_requested = |testkit: &AnchoringTestKit| {
+        BtcAnchoringSchema::new(testkit.snapshot())
+            .sweep_requests()
+            .contains(&stray_tx.id())
+    };
+    let requests = anchoring_testkit.create_sweep_request_for_validators(stray_tx.clone(), 4);
+    anchoring_testkit.create_block_with_transactions(requests);
+    assert!(is_requested(&anchoring_testkit));
+
+    let signatures = anchoring_testkit
+        .create_sweep_signature_tx_for_validators(1)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    assert!(is_requested(&anchoring_testkit));
+
+    anchoring_testkit.create_blocks_until(Height(21 + SWEEP_REQUEST_LIFETIME));
+    assert!(!is_requested(&anchoring_testkit));
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert!(schema.sweep_signatures().iter().next().is_none());
+    assert!(schema.sweep_transactions().iter().next().is_none());
+
+    // The expired request can be approved again and fulfilled by the retired signers,
+    // including the ones that are not the actual signers anymore.
+    let requests = anchoring_testkit.create_sweep_request_for_validators(stray_tx.clone(), 4);
+    anchoring_testkit.create_block_with_transactions(requests);
+    assert!(is_requested(&anchoring_testkit));
+    anchoring_testkit.create_block();
+
+    let signatures = anchoring_testkit
+        .create_sweep_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    let sweep_tx = schema.sweep_transactions().values().next().unwrap();
+    assert_eq!(sweep_tx.0.input[0].previous_output.txid, stray_tx.0.txid());
+    assert_eq!(
+        sweep_tx.0.output[0].script_pubkey,
+        actual_address.script_pubkey()
+    );
+    assert!(schema.spent_funding_transactions().contains(&stray_tx.id()));
+    assert!(!is_requested(&anchoring_testkit));
+}